@@ -0,0 +1,24 @@
+#version 100
+precision mediump float;
+
+varying vec4 color;
+varying vec2 uv;
+
+uniform sampler2D Texture;
+uniform vec2 Resolution;
+uniform float Intensity;
+
+// Bright colours bleed a soft glow into their surroundings
+
+void main() {
+    vec2 texel = 2.0 / Resolution;
+    vec3 glow = vec3(0.0);
+    for (int x = -3; x <= 3; x++) {
+        for (int y = -3; y <= 3; y++) {
+            vec3 near = texture2D(Texture, uv + vec2(float(x), float(y)) * texel).rgb;
+            glow += max(near - 0.45, 0.0);
+        }
+    }
+    vec3 res = texture2D(Texture, uv).rgb + glow / 49.0 * 2.5 * Intensity;
+    gl_FragColor = vec4(res * color.rgb, 1.0);
+}
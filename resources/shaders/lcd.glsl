@@ -0,0 +1,26 @@
+#version 100
+precision mediump float;
+
+varying vec4 color;
+varying vec2 uv;
+
+uniform sampler2D Texture;
+uniform vec2 Resolution;
+uniform float Intensity;
+
+// A handheld LCD: the picture is split into cells of 3 by 3 pixels with dark gaps between them
+// and a red, green and blue stripe in each
+
+void main() {
+    vec2 cell = fract(uv * Resolution / 3.0);
+    float gap = step(0.67, max(cell.x, cell.y));
+    vec3 stripe = vec3(
+        step(cell.x, 0.33),
+        step(0.33, cell.x) * step(cell.x, 0.67),
+        step(0.67, cell.x)
+    );
+    vec3 res = texture2D(Texture, uv).rgb;
+    res *= mix(vec3(1.0), 0.75 + 0.4 * stripe, 0.5 * Intensity);
+    res *= 1.0 - 0.4 * gap * Intensity;
+    gl_FragColor = vec4(res * color.rgb, 1.0);
+}
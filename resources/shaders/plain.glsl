@@ -0,0 +1,13 @@
+#version 100
+precision lowp float;
+
+varying vec4 color;
+varying vec2 uv;
+
+uniform sampler2D Texture;
+
+// The game as it is drawn, with no effect at all
+
+void main() {
+    gl_FragColor = vec4(texture2D(Texture, uv).rgb * color.rgb, 1.0);
+}
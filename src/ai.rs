@@ -0,0 +1,220 @@
+//! A heuristic player. Every rotation of the current piece is moved to every column it can reach
+//! from where it spawns and dropped there, and the board each placement leaves is scored on its
+//! height, holes, bumpiness and the lines it cleared.
+
+use std::collections::VecDeque;
+
+use crate::{
+    constants::*,
+    data::Vec2,
+    grid::Grid,
+    player::{Input, Player},
+    tetromino::{Rotation, TType, Tetromino},
+};
+
+// Weights for the features of the board a placement leaves behind
+const HEIGHT_WEIGHT: f32 = -0.51;
+const LINES_WEIGHT: f32 = 0.76;
+const HOLES_WEIGHT: f32 = -0.36;
+const BUMPINESS_WEIGHT: f32 = -0.18;
+
+/// Where a piece should go: how it is turned and the column of its leftmost cell
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Placement {
+    pub rotation: usize,
+    pub column: i32,
+}
+
+impl Placement {
+    /// Reads a `PLACE <rotation> <column>` line
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid placement `{line}`");
+        let args = line.split_whitespace().collect::<Vec<_>>();
+        let ["PLACE", rotation, column] = args[..] else {
+            return Err(invalid());
+        };
+        let rotation = rotation.parse::<usize>().map_err(|_| invalid())?;
+        let column = column.parse::<i32>().map_err(|_| invalid())?;
+        if rotation > 3 {
+            return Err(invalid());
+        }
+        Ok(Self { rotation, column })
+    }
+
+    pub fn encode(&self) -> String {
+        format!("PLACE {} {}", self.rotation, self.column)
+    }
+
+    /// The presses that take `piece` from where it is now to this placement, one a frame: turned
+    /// where it is, then moved sideways. The piece is meant to be soft dropped once they run out.
+    pub fn presses(&self, piece: &Tetromino) -> VecDeque<Input> {
+        // Lets go of soft drop first, it stays ignored for a new piece until released
+        let mut presses = VecDeque::from([Input::default()]);
+        let mut target = *piece;
+        let turns = (self.rotation + 4 - piece.rotation as usize) % 4;
+        for _ in 0..turns {
+            target.rotate();
+        }
+        for _ in 0..turns {
+            presses.push_back(Input {
+                rotate: true,
+                ..Default::default()
+            });
+        }
+        let shift = self.column - leftmost(&target);
+        for _ in 0..shift.abs() {
+            presses.push_back(Input {
+                left: shift < 0,
+                right: shift > 0,
+                ..Default::default()
+            });
+        }
+        presses
+    }
+}
+
+fn leftmost(piece: &Tetromino) -> i32 {
+    piece
+        .get_curr_positions()
+        .iter()
+        .map(|tile| tile.col as i32)
+        .min()
+        .unwrap()
+}
+
+/// Whether `piece` is on the board, hidden rows included, without overlapping the stack
+fn fits(grid: &Grid, piece: &Tetromino) -> bool {
+    piece
+        .get_curr_positions()
+        .iter()
+        .all(|tile| grid.is_cell_contained(tile) && grid.is_empty(tile))
+}
+
+/// Where a piece of `_type` is once it came in, dropped a row like `Player` does when it can
+fn spawned(grid: &Grid, _type: TType) -> Tetromino {
+    let mut piece = Tetromino::new(_type);
+    piece.move_pos(Vec2::new(1., 0.));
+    if !fits(grid, &piece) {
+        piece.move_pos(Vec2::new(-1., 0.));
+    }
+    piece
+}
+
+/// Where `piece` lands when dropped straight down from where it is
+fn drop(grid: &Grid, mut piece: Tetromino) -> Tetromino {
+    loop {
+        piece.move_pos(Vec2::new(1., 0.));
+        if !fits(grid, &piece) {
+            piece.move_pos(Vec2::new(-1., 0.));
+            return piece;
+        }
+    }
+}
+
+fn score(grid: &Grid, piece: &Tetromino) -> f32 {
+    let mut values = grid.all_values().to_vec();
+    for tile in piece.get_curr_positions() {
+        values[(tile.row + buffer_rows() as f32) as usize][tile.col as usize] = Some(piece._type);
+    }
+    let before = values.len();
+    values.retain(|row| row.iter().any(Option::is_none));
+    let lines = before - values.len();
+
+    let heights = (0..num_cols())
+        .map(|col| {
+            let top = values.iter().position(|row| row[col].is_some());
+            top.map_or(0, |top| values.len() - top)
+        })
+        .collect::<Vec<_>>();
+    let holes = (0..num_cols())
+        .map(|col| {
+            values
+                .iter()
+                .skip_while(|row| row[col].is_none())
+                .filter(|row| row[col].is_none())
+                .count()
+        })
+        .sum::<usize>();
+    let bumpiness = heights
+        .windows(2)
+        .map(|pair| pair[0].abs_diff(pair[1]))
+        .sum::<usize>();
+
+    HEIGHT_WEIGHT * heights.iter().sum::<usize>() as f32
+        + LINES_WEIGHT * lines as f32
+        + HOLES_WEIGHT * holes as f32
+        + BUMPINESS_WEIGHT * bumpiness as f32
+}
+
+/// Every position `piece` gets to by moving one way a column at a time, stopping at the first
+/// one that is in the way like `Player::update` does, with `piece` itself first
+fn reachable(grid: &Grid, piece: Tetromino) -> Vec<Tetromino> {
+    let mut positions = vec![piece];
+    for step in [-1., 1.] {
+        let mut moved = piece;
+        loop {
+            moved.move_pos(Vec2::new(0., step));
+            if !fits(grid, &moved) {
+                break;
+            }
+            positions.push(moved);
+        }
+    }
+    positions
+}
+
+/// The best spot the current piece can get to from where it spawns with where it lands, or
+/// `None` when it fits nowhere
+pub fn best_placement(player: &Player) -> Option<(Placement, Tetromino)> {
+    let grid = &player.grid;
+    let mut piece = spawned(grid, player.selector.current._type);
+    if !fits(grid, &piece) {
+        return None;
+    }
+    let mut best: Option<(f32, Placement, Tetromino)> = None;
+
+    // Turned where it spawned, one turn at a time, so a turn that is in the way blocks the rest
+    for turn in 0..Rotation::ALL.len() {
+        if turn > 0 {
+            piece.rotate();
+            if !fits(grid, &piece) {
+                break;
+            }
+        }
+        for moved in reachable(grid, piece) {
+            let landed = drop(grid, moved);
+            let score = score(grid, &landed);
+            if best.is_none_or(|(best, ..)| score > best) {
+                let placement = Placement {
+                    rotation: landed.rotation as usize,
+                    column: leftmost(&landed),
+                };
+                best = Some((score, placement, landed));
+            }
+        }
+    }
+    best.map(|(_, placement, landed)| (placement, landed))
+}
+
+/// Plays for a player, planning each piece as it comes
+#[derive(Default)]
+pub struct Autopilot {
+    // Pieces placed when the plan was made, a change means there is a new piece to plan for
+    planned_at: Option<u32>,
+    presses: VecDeque<Input>,
+}
+
+impl Autopilot {
+    pub fn poll(&mut self, player: &Player) -> Input {
+        if self.planned_at != Some(player.data.pieces) {
+            self.planned_at = Some(player.data.pieces);
+            self.presses = best_placement(player)
+                .map(|(placement, _)| placement.presses(&player.selector.current))
+                .unwrap_or_default();
+        }
+        self.presses.pop_front().unwrap_or(Input {
+            soft_drop: true,
+            ..Default::default()
+        })
+    }
+}
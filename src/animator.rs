@@ -1,34 +1,257 @@
 use macroquad::{color::Color, time::get_time};
 use rustc_hash::FxHashMap;
 
+/// Every animation there is, so they are told apart by name instead of by number
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Anim {
+    /// The game over screen coming in, and the blinking of its hints after
+    GameOver,
+    /// The blinking outline of the ghost piece
+    Ghost,
+    FinesseFault,
+    OpenerMistake,
+    /// Short notices like "SAVED" in the editor and on the game over screen
+    Message,
+    Shake,
+    TetrisFlash,
+    /// A cleared row collapsing, by an id from [`AnimationRegulator::allocate_id`]
+    RowClear(u16),
+    /// A cell of the invisible stack fading out, by row and column
+    CellFade(usize, usize),
+}
+
+/// How an animation speeds up and slows down on its way from start to end
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Ease {
+    #[default]
+    Linear,
+    /// Starts slow and speeds up
+    In,
+    /// Starts fast and slows down
+    Out,
+    InOut,
+    /// Bounces against the end a few times before settling on it
+    Bounce,
+    /// Springs past the end and wobbles around it
+    Elastic,
+}
+
+impl Ease {
+    /// Maps how far along an animation is, from 0 to 1, to how far along it should look
+    pub fn apply(self, t: f64) -> f64 {
+        let t = t.clamp(0., 1.);
+        match self {
+            Self::Linear => t,
+            Self::In => t * t * t,
+            Self::Out => 1. - (1. - t).powi(3),
+            Self::InOut if t < 0.5 => 4. * t * t * t,
+            Self::InOut => 1. - (-2. * t + 2.).powi(3) / 2.,
+            Self::Bounce => {
+                let (n, d) = (7.5625, 2.75);
+                if t < 1. / d {
+                    n * t * t
+                } else if t < 2. / d {
+                    let t = t - 1.5 / d;
+                    n * t * t + 0.75
+                } else if t < 2.5 / d {
+                    let t = t - 2.25 / d;
+                    n * t * t + 0.9375
+                } else {
+                    let t = t - 2.625 / d;
+                    n * t * t + 0.984375
+                }
+            }
+            Self::Elastic if t == 0. || t == 1. => t,
+            Self::Elastic => {
+                let period = std::f64::consts::TAU / 3.;
+                2f64.powf(-10. * t) * ((t * 10. - 0.75) * period).sin() + 1.
+            }
+        }
+    }
+}
+
+/// One step of a [`Sequence`], from `from` to `to` over `duration` seconds
+#[derive(Debug, Clone, Copy)]
+struct Tween {
+    from: f64,
+    to: f64,
+    duration: f64,
+    ease: Ease,
+}
+
+/// A value changing over time, built up from steps that play one after the other:
+///
+/// ```text
+/// // Shown for a second, then faded out
+/// Sequence::starting_at(1.).hold(1.).then(0., 0.5, Ease::Out)
+/// ```
+#[derive(Debug, Clone)]
+pub struct Sequence {
+    start: f64,
+    tweens: Vec<Tween>,
+}
+
+impl Sequence {
+    pub fn starting_at(value: f64) -> Self {
+        Self {
+            start: value,
+            tweens: Vec::new(),
+        }
+    }
+
+    /// Goes from 0 to 1 at a steady pace over `duration`
+    pub fn ratio(duration: f64) -> Self {
+        Self::starting_at(0.).then(1., duration, Ease::Linear)
+    }
+
+    fn end(&self) -> f64 {
+        self.tweens.last().map_or(self.start, |tween| tween.to)
+    }
+
+    /// Moves on from where the steps before left off to `to`
+    pub fn then(mut self, to: f64, duration: f64, ease: Ease) -> Self {
+        self.tweens.push(Tween {
+            from: self.end(),
+            to,
+            duration,
+            ease,
+        });
+        self
+    }
+
+    /// Stays where the steps before left off for `duration`
+    pub fn hold(self, duration: f64) -> Self {
+        let end = self.end();
+        self.then(end, duration, Ease::Linear)
+    }
+
+    /// Plays `other` after this, starting from wherever `other` starts
+    pub fn chain(mut self, other: Sequence) -> Self {
+        self = self.then(other.start, 0., Ease::Linear);
+        self.tweens.extend(other.tweens);
+        self
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.tweens.iter().map(|tween| tween.duration).sum()
+    }
+
+    /// The value `elapsed` seconds in, which stays at the end once every step is done
+    pub fn value(&self, mut elapsed: f64) -> f64 {
+        for tween in &self.tweens {
+            if elapsed < tween.duration {
+                let t = tween.ease.apply(elapsed / tween.duration);
+                return tween.from + (tween.to - tween.from) * t;
+            }
+            elapsed -= tween.duration;
+        }
+        self.end()
+    }
+}
+
 #[derive(Default)]
 pub struct AnimationRegulator {
-    // (duration, start_time)
-    data: FxHashMap<u16, (f64, f64)>,
+    // (sequence, start_time)
+    data: FxHashMap<Anim, (Sequence, f64)>,
+    // Ids handed back by the animations that had them, to be handed out again first
+    free_ids: Vec<u16>,
+    // The lowest id that was never handed out
+    next_id: u16,
+    // Without a window there is no clock to read, so time stands still
+    headless: bool,
 }
 
 impl AnimationRegulator {
-    pub fn remove_animation(&mut self, id: u16) {
-        self.data.remove(&id);
+    /// For running games without a window
+    pub fn headless() -> Self {
+        Self {
+            headless: true,
+            ..Default::default()
+        }
     }
-    pub fn reset_animation(&mut self, id: u16, duration: f64) {
-        self.data.insert(id, (duration, get_time()));
+
+    pub fn is_headless(&self) -> bool {
+        self.headless
     }
-    pub fn animation_completed_ratio(&self, id: u16) -> f64 {
-        if let Some((duration, start_time)) = self.data.get(&id) {
-            return (1. - (((start_time + duration) - get_time()) / duration)).clamp(0., 1.);
+
+    pub fn now(&self) -> f64 {
+        match self.headless {
+            true => 0.,
+            false => get_time(),
         }
-        1.
     }
 
-    pub fn colour_map(&self, id: u16, mut colour: Color) -> Color {
-        colour.a = (self.animation_completed_ratio(id)) as f32;
+    /// An id no other animation has, for animations that can play several times at once
+    pub fn allocate_id(&mut self) -> u16 {
+        self.free_ids.pop().unwrap_or_else(|| {
+            self.next_id += 1;
+            self.next_id - 1
+        })
+    }
+
+    /// Hands back an id from `allocate_id` once its animation is done with it
+    pub fn release_id(&mut self, id: u16) {
+        self.free_ids.push(id);
+    }
+
+    /// Starts `anim` over, going from 0 to 1 over `duration`
+    pub fn start(&mut self, anim: Anim, duration: f64) {
+        self.play(anim, Sequence::ratio(duration));
+    }
+
+    /// Starts `anim` over with the steps of `sequence`
+    pub fn play(&mut self, anim: Anim, sequence: Sequence) {
+        self.data.insert(anim, (sequence, self.now()));
+    }
+
+    pub fn stop(&mut self, anim: Anim) {
+        self.data.remove(&anim);
+    }
+
+    /// Whether `anim` was started and hasn't finished yet
+    pub fn is_running(&self, anim: Anim) -> bool {
+        self.time_remaining(anim) > 0.
+    }
+
+    /// How far along `anim` is from 0 to 1, which is 1 for one that was never started
+    pub fn progress(&self, anim: Anim) -> f64 {
+        match self.data.get(&anim) {
+            Some((sequence, start_time)) if sequence.duration() > 0. => {
+                ((self.now() - start_time) / sequence.duration()).clamp(0., 1.)
+            }
+            _ => 1.,
+        }
+    }
+
+    /// How far along `anim` is, along `ease`
+    pub fn eased(&self, anim: Anim, ease: Ease) -> f64 {
+        ease.apply(self.progress(anim))
+    }
+
+    /// The current value of the sequence `anim` plays, when it was started
+    pub fn value(&self, anim: Anim) -> Option<f64> {
+        let (sequence, start_time) = self.data.get(&anim)?;
+        Some(sequence.value(self.now() - start_time))
+    }
+
+    pub fn time_remaining(&self, anim: Anim) -> f64 {
+        if let Some((sequence, start_time)) = self.data.get(&anim) {
+            return ((start_time + sequence.duration()) - self.now()).max(0.);
+        }
+        0.
+    }
+
+    /// `colour` faded in along `ease` as `anim` plays
+    pub fn fade_in(&self, anim: Anim, mut colour: Color, ease: Ease) -> Color {
+        colour.a = self.eased(anim, ease) as f32;
         colour
     }
 
-    pub fn as_blink(&self, id: u16) -> f64 {
-        if let Some((duration, start_time)) = self.data.get(&id) {
-            let a = (start_time + duration) - get_time();
+    /// Goes back and forth between 0 and 1, once every length of `anim`, for as long as it exists
+    pub fn blink(&self, anim: Anim) -> f64 {
+        if let Some((sequence, start_time)) = self.data.get(&anim) {
+            let duration = sequence.duration();
+            let a = (start_time + duration) - self.now();
             let (div, modl) = (a / duration, a % duration);
             let val = (modl / duration).abs();
             return match div.abs() as u32 % 2 == 0 {
@@ -40,9 +263,8 @@ impl AnimationRegulator {
         1.
     }
 
-    pub fn colour_blink_map(&self, id: u16, colour: Color) -> Color {
-        let mut colour = colour;
-        colour.a = (self.as_blink(id)) as f32;
+    pub fn blink_colour(&self, anim: Anim, mut colour: Color) -> Color {
+        colour.a = self.blink(anim) as f32;
         colour
     }
 }
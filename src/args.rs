@@ -0,0 +1,194 @@
+use crate::{
+    constants::{self, BOARD_COLS_RANGE, BOARD_ROWS_RANGE},
+    fumen::{self, Fumen},
+    net, stream,
+};
+
+pub const USAGE: &str = "\
+Usage: tetrs [OPTIONS]
+       tetrs sim [OPTIONS]  Play games without a window and print their stats, see `tetrs sim --help`
+
+Options:
+  --board <WxH>    Play on a board W columns wide and H rows high (default 10x20)
+  --fumen <DATA>   Open the board editor on a fumen (v115) position
+  --host [ADDR]    Wait for someone to join an online versus game (default 0.0.0.0:7878)
+  --join <ADDR>    Join an online versus game hosted at ADDR, the port defaults to 7878
+  --bot <COMMAND>  Let an external program play single player games over stdin and stdout
+  --stream [ADDR]  Broadcast the live game as JSON lines to anyone connecting (default 127.0.0.1:7879)
+  --effect <NAME>  Draw the game through crt, bloom, lcd, plain or one of your own shaders
+  --theme <NAME>   Colours, font and blocks to draw with, Tetrs, Classic, Midnight, Protanopia,
+                   Deuteranopia, Tritanopia or your own
+  -h, --help       Print this message";
+
+pub const SIM_USAGE: &str = "\
+Usage: tetrs sim [OPTIONS]
+
+Plays games as fast as possible without a window, with the built-in AI or from a replay, and
+prints the score, lines, pieces and tetris rate of every game
+
+Options:
+  --games <N>       How many games the AI plays (default 10)
+  --seed <N>        Seed of the first game, the ones after it count up from there (default 0)
+  --board <WxH>     Board size, as for the game (default 10x20)
+  --max-pieces <N>  End a game after this many pieces (default 1000 for the AI, none for replays)
+  --format <FMT>    csv or json, one line for each game (default csv)
+  --replay <FILE>   Replay the games in FILE instead of letting the AI play
+  --record <FILE>   Save the games played as a replay to FILE
+  -h, --help        Print this message";
+
+/// Where to host or join an online versus game
+pub enum Online {
+    Host(String),
+    Join(String),
+}
+
+// Lets an address be given without its port
+fn with_port(address: &str, port: u16) -> String {
+    match address.contains(':') {
+        true => address.to_owned(),
+        false => format!("{address}:{port}"),
+    }
+}
+
+/// Reads a `WxH` board size and uses it for the rest of the run
+fn set_board_size(value: &str) -> Result<(), String> {
+    let invalid = || format!("board size `{value}` has to look like 10x20");
+    let (cols, rows) = value.split_once(['x', 'X']).ok_or_else(invalid)?;
+    let cols = cols.parse().map_err(|_| invalid())?;
+    let rows = rows.parse().map_err(|_| invalid())?;
+    if !BOARD_COLS_RANGE.contains(&cols) || !BOARD_ROWS_RANGE.contains(&rows) {
+        return Err(format!(
+            "boards can be {} to {} columns wide and {} to {} rows high",
+            BOARD_COLS_RANGE.start(),
+            BOARD_COLS_RANGE.end(),
+            BOARD_ROWS_RANGE.start(),
+            BOARD_ROWS_RANGE.end()
+        ));
+    }
+    constants::set_board_size(cols, rows);
+    Ok(())
+}
+
+#[derive(Default)]
+pub struct Args {
+    pub fumen: Option<Fumen>,
+    pub online: Option<Online>,
+    pub stream: Option<String>,
+    pub bot: Option<String>,
+    pub effect: Option<String>,
+    pub theme: Option<String>,
+}
+
+impl Args {
+    pub fn parse() -> Result<Self, String> {
+        let mut parsed = Self::default();
+        let mut args = std::env::args().skip(1).peekable();
+        let mut fumen = None;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--board" => set_board_size(&args.next().ok_or("--board needs a size")?)?,
+                "--fumen" => fumen = Some(args.next().ok_or("--fumen needs a value")?),
+                "--host" => {
+                    let address = args
+                        .next_if(|arg| !arg.starts_with('-'))
+                        .unwrap_or_else(|| "0.0.0.0".to_owned());
+                    parsed.online = Some(Online::Host(with_port(&address, net::DEFAULT_PORT)));
+                }
+                "--join" => {
+                    let address = args.next().ok_or("--join needs an address")?;
+                    parsed.online = Some(Online::Join(with_port(&address, net::DEFAULT_PORT)));
+                }
+                "--bot" => {
+                    parsed.bot = Some(args.next().ok_or("--bot needs a command")?);
+                }
+                "--stream" => {
+                    let address = args
+                        .next_if(|arg| !arg.starts_with('-'))
+                        .unwrap_or_else(|| "127.0.0.1".to_owned());
+                    parsed.stream = Some(with_port(&address, stream::DEFAULT_PORT));
+                }
+                "--effect" => {
+                    parsed.effect = Some(args.next().ok_or("--effect needs a name")?);
+                }
+                "--theme" => {
+                    parsed.theme = Some(args.next().ok_or("--theme needs a name")?);
+                }
+                "-h" | "--help" => {
+                    println!("{USAGE}");
+                    std::process::exit(0);
+                }
+                _ => return Err(format!("unknown argument `{arg}`")),
+            }
+        }
+        // Only read once the board size is known, whichever order the arguments came in
+        if let Some(data) = fumen {
+            parsed.fumen = Some(fumen::decode(&data)?);
+        }
+        Ok(parsed)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+pub struct SimArgs {
+    pub games: usize,
+    pub seed: u64,
+    pub max_pieces: Option<u32>,
+    pub format: Format,
+    pub replay: Option<String>,
+    pub record: Option<String>,
+}
+
+impl Default for SimArgs {
+    fn default() -> Self {
+        Self {
+            games: 10,
+            seed: 0,
+            max_pieces: None,
+            format: Format::Csv,
+            replay: None,
+            record: None,
+        }
+    }
+}
+
+impl SimArgs {
+    /// Reads the arguments after `sim`
+    pub fn parse() -> Result<Self, String> {
+        let mut parsed = Self::default();
+        let mut args = std::env::args().skip(2);
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{arg} needs a value"));
+            let number = |value: String| {
+                value
+                    .parse()
+                    .map_err(|_| format!("{arg} needs a number, not `{value}`"))
+            };
+            match arg.as_str() {
+                "--board" => set_board_size(&value()?)?,
+                "--games" => parsed.games = number(value()?)? as usize,
+                "--seed" => parsed.seed = number(value()?)?,
+                "--max-pieces" => parsed.max_pieces = Some(number(value()?)? as u32),
+                "--format" => {
+                    parsed.format = match value()?.as_str() {
+                        "csv" => Format::Csv,
+                        "json" => Format::Json,
+                        other => return Err(format!("unknown format `{other}`")),
+                    }
+                }
+                "--replay" => parsed.replay = Some(value()?),
+                "--record" => parsed.record = Some(value()?),
+                "-h" | "--help" => {
+                    println!("{SIM_USAGE}");
+                    std::process::exit(0);
+                }
+                _ => return Err(format!("unknown argument `{arg}`")),
+            }
+        }
+        Ok(parsed)
+    }
+}
@@ -0,0 +1,171 @@
+//! Lets an external program play, talking over its stdin and stdout one line at a time:
+//!
+//! ```text
+//! game -> bot
+//! HELLO <version>                        once, when the bot is started
+//! PIECE <id> <board> <current> <next>... where should the current piece go?
+//! GAMEOVER <score> <lines> <pieces>      the game ended, a new one starts with the next PIECE
+//!
+//! bot -> game
+//! PLACE <id> <rotation> <column>         turn the piece `rotation` times from spawn (0 to 3) and
+//!                                        move it until its leftmost cell is in `column`
+//! ```
+//!
+//! Every PIECE has an id one higher than the one before, to be sent back with its PLACE. A piece
+//! can lock before its answer comes in, and answers for pieces that are gone are ignored.
+//!
+//! The board is `buffer_rows() + num_rows()` rows of `num_cols()` characters from the top, joined
+//! by `/`, with `.` for an empty cell, `X` for garbage and the piece letter otherwise. The hidden
+//! rows above the field come first, as many as there are visible ones, since blocks can lock
+//! there too. A placement is carried out
+//! with the same presses a player would make, one a frame, then the piece is soft dropped. Pieces
+//! keep falling while the bot thinks, and a placement that runs into the stack stops short, so
+//! bots play by the same rules as everyone else.
+
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+};
+
+use crate::{
+    ai::Placement,
+    player::{Input, Player},
+};
+
+// Bumped whenever a message changes
+pub const PROTOCOL_VERSION: u32 = 2;
+
+pub struct Bot {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    // Id of the last PIECE sent, the only one an answer is taken for
+    piece_id: u32,
+    asked: bool,
+    // Presses left for the current placement, the piece is soft dropped once they run out
+    plan: Option<VecDeque<Input>>,
+}
+
+impl Bot {
+    /// Starts `command`, split on whitespace into the program and its arguments
+    pub fn spawn(command: &str) -> Result<Self, String> {
+        let mut words = command.split_whitespace();
+        let program = words.next().ok_or("no bot command given")?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|err| format!("could not start bot `{command}`: {err}"))?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        // Read on another thread so a slow bot never holds up a frame
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut bot = Self {
+            child,
+            stdin,
+            lines,
+            piece_id: 0,
+            asked: false,
+            plan: None,
+        };
+        bot.send(&format!("HELLO {PROTOCOL_VERSION}"))?;
+        Ok(bot)
+    }
+
+    fn send(&mut self, line: &str) -> Result<(), String> {
+        writeln!(self.stdin, "{line}")
+            .and_then(|_| self.stdin.flush())
+            .map_err(|err| format!("could not talk to the bot: {err}"))
+    }
+
+    /// Forgets the placement in progress, for when the current piece changed
+    pub fn next_piece(&mut self) {
+        self.asked = false;
+        self.plan = None;
+    }
+
+    pub fn game_over(&mut self, player: &Player) -> Result<(), String> {
+        self.next_piece();
+        self.send(&format!(
+            "GAMEOVER {} {} {}",
+            player.data.score, player.data.lines, player.data.pieces
+        ))
+    }
+
+    /// What the bot presses this frame
+    pub fn poll(&mut self, player: &Player) -> Result<Input, String> {
+        if !self.asked {
+            self.asked = true;
+            self.piece_id += 1;
+            self.send(&format!("PIECE {} {}", self.piece_id, describe(player)))?;
+        }
+        while self.plan.is_none() {
+            match self.lines.try_recv() {
+                Ok(line) => {
+                    let (id, placement) = parse_answer(&line)?;
+                    if id == self.piece_id {
+                        self.plan = Some(placement.presses(&player.selector.current));
+                    }
+                }
+                Err(TryRecvError::Empty) => return Ok(Input::default()),
+                Err(TryRecvError::Disconnected) => return Err("the bot exited".to_owned()),
+            }
+        }
+        let plan = self.plan.as_mut().unwrap();
+        Ok(plan.pop_front().unwrap_or(Input {
+            soft_drop: true,
+            ..Default::default()
+        }))
+    }
+}
+
+impl Drop for Bot {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Reads a `PLACE <id> <rotation> <column>` line into the id of the piece and where it goes
+fn parse_answer(line: &str) -> Result<(u32, Placement), String> {
+    let invalid = || format!("invalid placement `{line}`");
+    let args = line.split_whitespace().collect::<Vec<_>>();
+    let ["PLACE", id, rotation, column] = args[..] else {
+        return Err(invalid());
+    };
+    let id = id.parse().map_err(|_| invalid())?;
+    Ok((id, Placement::parse(&format!("PLACE {rotation} {column}"))?))
+}
+
+fn describe(player: &Player) -> String {
+    let board = player
+        .grid
+        .all_values()
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|cell| cell.map_or('.', |t| t.to_char()))
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+    let pieces = std::iter::once(player.selector.current._type)
+        .chain(player.selector.preview())
+        .map(|t| t.to_char().to_string())
+        .collect::<Vec<_>>();
+    format!("{board} {}", pieces.join(" "))
+}
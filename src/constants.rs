@@ -1,106 +1,101 @@
-use lazy_static::lazy_static;
-use macroquad::color::Color;
+use std::sync::OnceLock;
 
 pub const WINDOW_HEIGHT: i32 = 620;
-pub const WINDOW_WIDTH: i32 = 500;
-
-pub const NUM_ROWS: usize = 20;
-pub const NUM_COLS: usize = 10;
-pub const CELL_SIZE: f32 = 30.;
+// The window is at least this wide, and wider when the board needs it
+pub const MIN_WINDOW_WIDTH: i32 = 500;
+// Room right of the board for the level, score and next piece
+pub const SIDE_PANEL_WIDTH: f32 = 160.;
+
+pub const DEFAULT_COLS: usize = 10;
+pub const DEFAULT_ROWS: usize = 20;
+pub const BOARD_COLS_RANGE: std::ops::RangeInclusive<usize> = 4..=30;
+pub const BOARD_ROWS_RANGE: std::ops::RangeInclusive<usize> = 4..=40;
+// Cells shrink from this size for boards that wouldn't fit the window otherwise
+pub const MAX_CELL_SIZE: f32 = 30.;
+
+// (columns, rows), set once at startup
+static BOARD_SIZE: OnceLock<(usize, usize)> = OnceLock::new();
+
+/// Sets the board size for the rest of the run, only the first call has any effect
+pub fn set_board_size(cols: usize, rows: usize) {
+    let _ = BOARD_SIZE.set((cols, rows));
+}
 
-pub const GAMEOVER_ANIM_DURATION: f64 = 1.;
-pub const ROW_DISSAPEAR_ANIM_DURATION: f64 = 0.8;
+pub fn num_cols() -> usize {
+    BOARD_SIZE.get().map_or(DEFAULT_COLS, |size| size.0)
+}
 
-pub const FONT: &[u8] = include_bytes!("../resources/fonts/FontRegular.otf");
+pub fn num_rows() -> usize {
+    BOARD_SIZE.get().map_or(DEFAULT_ROWS, |size| size.1)
+}
 
-macro_rules! use_colors {
-    ($(($name: ident, $col: expr)),+) => {
-        lazy_static! {$(pub static ref $name : Color = Color::from_hex($col);)*}
-    };
+/// Hidden rows above the visible field that pieces spawn into, as many as there are visible ones
+pub fn buffer_rows() -> usize {
+    num_rows()
 }
 
-use_colors!(
-    /*USED*/ (EERIE_BLACK, 0x202020),
-    /*USED*/ (COCOA_BROWN, 0x2d211e),
-    /*USED*/ (CRATER_BROWN, 0x452923),
-    /*USED*/ (PICKLED_BEAN, 0x6d3d29),
-    /*USED*/ (BROWN, 0xb16b4a),
-    /*USED*/ (GOLD_SAND, 0xe8be82),
-    /*USED*/ (CLAY_CREEK, 0x8e9257),
-    /*USED*/ (RAVEN, 0x707b88),
-    /*USED*/ (CASCADE, 0x8aa7ac),
-    /*USED*/ (BURNT_SIENNA1, 0xe55d4d),
-    /*USED*/ (BURNT_SIENNA2, 0xf1866c),
-    /*USED*/ (FLAME_PEA, 0xd26730),
-    /*USED*/ (GOLDEN_GRASS, 0xde9a28),
-    /*USED*/ (ZOMBIE, 0xe8d8a5)
-);
-
-/*
-| EERIE_BLACK | ![#202020](https://via.placeholder.com/10/202020?text=+) #202020|
-| COCOA_BROWN | ![#2d211e](https://via.placeholder.com/10/2d211e?text=+) #2d211e|
-| CRATER_BROWN | ![#452923](https://via.placeholder.com/10/452923?text=+) #452923|
-| PICKLED_BEAN | ![#6d3d29](https://via.placeholder.com/10/6d3d29?text=+) #6d3d29|
-| BROWN | ![#b16b4a](https://via.placeholder.com/10/b16b4a?text=+) #b16b4a|
-| GOLD_SAND | ![#e8be82](https://via.placeholder.com/10/e8be82?text=+) #e8be82|
-| CLAY_CREEK | ![#8e9257](https://via.placeholder.com/10/8e9257?text=+) #8e9257|
-| RAVEN | ![#707b88](https://via.placeholder.com/10/707b88?text=+) #707b88|
-| CASCADE | ![#8aa7ac](https://via.placeholder.com/10/8aa7ac?text=+) #8aa7ac|
-| BURNT_SIENNA1 | ![#e55d4d](https://via.placeholder.com/10/e55d4d?text=+) #e55d4d|
-| BURNT_SIENNA2 | ![#f1866c](https://via.placeholder.com/10/f1866c?text=+) #f1866c|
-| FLAME_PEA | ![#d26730](https://via.placeholder.com/10/d26730?text=+) #d26730|
-| GOLDEN_GRASS | ![#de9a28](https://via.placeholder.com/10/de9a28?text=+) #de9a28|
-| ZOMBIE | ![#e8d8a5](https://via.placeholder.com/10/e8d8a5?text=+) #e8d8a5|
-*/
-
-pub const FRAGMENT_SHADER: &str = r#"#version 100
-precision lowp float;
-
-varying vec4 color;
-varying vec2 uv;
-
-uniform sampler2D Texture;
-
-// https://www.shadertoy.com/view/XtlSD7
-
-vec2 CRTCurveUV(vec2 uv)
-{
-    uv = uv * 2.0 - 1.0;
-    vec2 offset = abs( uv.yx ) / vec2( 6.0, 4.0 );
-    uv = uv + uv * offset * offset;
-    uv = uv * 0.5 + 0.5;
-    return uv;
+pub fn cell_size() -> f32 {
+    let fit = 600. / num_rows().max(num_cols()) as f32;
+    fit.min(MAX_CELL_SIZE).floor()
 }
 
-void DrawVignette( inout vec3 color, vec2 uv )
-{
-    float vignette = uv.x * uv.y * ( 1.0 - uv.x ) * ( 1.0 - uv.y );
-    vignette = clamp( pow( 16.0 * vignette, 0.3 ), 0.0, 1.0 );
-    color *= vignette;
+/// Width of the board in pixels, with the margin around it
+pub fn board_width() -> f32 {
+    num_cols() as f32 * cell_size() + 22.
 }
 
+/// Left edge of the side panel
+pub fn panel_x() -> f32 {
+    board_width() + 18.
+}
 
-void DrawScanline( inout vec3 color, vec2 uv )
-{
-    float iTime = 0.1;
-    float scanline 	= clamp( 0.95 + 0.05 * cos( 3.14 * ( uv.y + 0.008 * iTime ) * 240.0 * 1.0 ), 0.0, 1.0 );
-    float grille 	= 0.85 + 0.15 * clamp( 1.5 * cos( 3.14 * uv.x * 640.0 * 1.0 ), 0.0, 1.0 );
-    color *= scanline * grille * 1.2;
+pub fn window_width() -> i32 {
+    (board_width() + SIDE_PANEL_WIDTH + 18.).max(MIN_WINDOW_WIDTH as f32) as i32
 }
 
-void main() {
-    vec2 crtUV = CRTCurveUV(uv);
-    vec3 res = texture2D(Texture, uv).rgb * color.rgb;
-    if (crtUV.x < 0.0 || crtUV.x > 1.0 || crtUV.y < 0.0 || crtUV.y > 1.0)
-    {
-        res = vec3(0.0, 0.0, 0.0);
-    }
-    DrawVignette(res, crtUV);
-    DrawScanline(res, uv);
-    gl_FragColor = vec4(res, 1.0);
+pub const GAMEOVER_ANIM_DURATION: f64 = 1.;
+pub const ROW_DISSAPEAR_ANIM_DURATION: f64 = 0.8;
 
+// Invisible mode: a locked cell stays visible for CELL_FADE_DELAY seconds, then fades out
+pub const CELL_FADE_DELAY: f64 = 3.;
+pub const CELL_FADE_DURATION: f64 = 1.;
+pub const STACK_REVEAL_DURATION: f64 = 1.5;
+pub const STACK_REVEAL_FADE_IN: f64 = 0.15;
+
+pub const FINESSE_FAULT_ANIM_DURATION: f64 = 1.;
+pub const OPENER_MISTAKE_ANIM_DURATION: f64 = 1.;
+// Short notices like "SAVED" in the editor and on the game over screen
+pub const MESSAGE_ANIM_DURATION: f64 = 1.5;
+pub const SHAKE_ANIM_DURATION: f64 = 0.35;
+// Pixels the screen shakes by for every line cleared at once
+pub const SHAKE_PER_LINE: f32 = 2.5;
+pub const TETRIS_FLASH_ANIM_DURATION: f64 = 0.4;
+// How fast the drawn piece catches up with the real one, the share of the way left shrinks by e
+// this many times a second
+pub const GLIDE_SPEED: f32 = 30.;
+// Cells a tile can be behind before it jumps instead, like when a piece is put back at the top
+pub const GLIDE_SNAP: f32 = 4.;
+
+// Seconds the title screen waits without a key press before the AI starts a demo game
+pub const ATTRACT_DELAY: f64 = 20.;
+
+// How many placements practice modes can step back through
+pub const UNDO_LIMIT: usize = 100;
+
+// Versus: garbage sent for clearing 0 to 4 lines at once, and 0 to 3 lines with a T-spin
+pub const ATTACK_TABLE: [u32; 5] = [0, 0, 1, 2, 4];
+pub const TSPIN_ATTACK_TABLE: [u32; 4] = [0, 2, 4, 6];
+pub const PERFECT_CLEAR_ATTACK: u32 = 10;
+// Both boards are drawn at this size or smaller so they fit side by side
+pub const VERSUS_SCALE: f32 = 0.72;
+
+/// The scale versus boards are drawn at, shrunk for boards too wide to fit at `VERSUS_SCALE`
+pub fn versus_scale() -> f32 {
+    let fit = (window_width() as f32 / 2. - 8.) / (board_width() + 5.);
+    fit.min(VERSUS_SCALE)
 }
-"#;
+
+pub const FONT: &[u8] = include_bytes!("../resources/fonts/FontRegular.otf");
 
 pub const VERTEX_SHADER: &str = "#version 100
 attribute vec3 position;
@@ -1,8 +1,6 @@
 use std::ops::Add;
 
-use macroquad::audio::play_sound_once;
-
-use crate::sounds::Sounds;
+use crate::{sounds::Sounds, storage};
 
 #[derive(Debug, Clone, Copy)]
 pub struct Vec2 {
@@ -33,15 +31,80 @@ impl Add for Vec2 {
 #[derive(Default, PartialEq, Eq)]
 pub enum GameState {
     #[default]
+    Menu,
     Playing,
     GameOver,
+    Solved,
+    Editing,
+    Connecting,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameMode {
+    #[default]
+    Classic,
+    Invisible,
+    Zen,
+    Puzzle,
+    Finesse,
+    Opener,
+    Editor,
+    Versus,
 }
 
+impl GameMode {
+    pub const ALL: [GameMode; 8] = [
+        GameMode::Classic,
+        GameMode::Invisible,
+        GameMode::Zen,
+        GameMode::Puzzle,
+        GameMode::Finesse,
+        GameMode::Opener,
+        GameMode::Editor,
+        GameMode::Versus,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Classic => "CLASSIC",
+            GameMode::Invisible => "INVISIBLE",
+            GameMode::Zen => "ZEN",
+            GameMode::Puzzle => "PUZZLE",
+            GameMode::Finesse => "FINESSE",
+            GameMode::Opener => "OPENER",
+            GameMode::Editor => "EDITOR",
+            GameMode::Versus => "VERSUS",
+        }
+    }
+
+    /// Practice modes let placements be undone
+    pub fn is_practice(&self) -> bool {
+        matches!(
+            self,
+            GameMode::Zen
+                | GameMode::Puzzle
+                | GameMode::Finesse
+                | GameMode::Opener
+                | GameMode::Editor
+        )
+    }
+}
+
+#[derive(Clone)]
 pub struct GameData {
     pub level: u8,
     pub score: u32,
+    pub lines: u32,
+    pub pieces: u32,
+    // How many of each piece were placed, in the order of `TType`
+    pub placed: [u32; 7],
+    // Lines the clears so far would have sent in versus
+    pub attack: u32,
+    // Pieces in a row that cleared lines, the last one included
+    pub streak: u32,
     frames_since_last_fall: u16,
     collected: u16,
+    fixed_level: bool,
 }
 
 impl Default for GameData {
@@ -49,13 +112,45 @@ impl Default for GameData {
         Self {
             level: 0,
             score: 0,
+            lines: 0,
+            pieces: 0,
+            placed: [0; 7],
+            attack: 0,
+            streak: 0,
             frames_since_last_fall: 0,
             collected: 9,
+            fixed_level: false,
         }
     }
 }
 
+const ZEN_SAVE: &str = "zen.txt";
+
 impl GameData {
+    /// Zen mode never speeds up and carries its score and lines over from the last session
+    pub fn zen(level: u8) -> Self {
+        let mut data = Self {
+            level,
+            fixed_level: true,
+            ..Default::default()
+        };
+        for (key, value) in storage::parse_pairs(&storage::read(ZEN_SAVE).unwrap_or_default()) {
+            match key {
+                "score" => data.score = value.parse().unwrap_or(0),
+                "lines" => data.lines = value.parse().unwrap_or(0),
+                _ => (),
+            }
+        }
+        data
+    }
+
+    pub fn save_zen(&self) {
+        storage::write(
+            ZEN_SAVE,
+            &format!("score = {}\nlines = {}\n", self.score, self.lines),
+        );
+    }
+
     pub fn frames_per_drop(&self) -> u16 {
         match self.level {
             0 => 48,
@@ -88,9 +183,9 @@ impl GameData {
 
     pub fn inc_level(&mut self, sounds: &Sounds) {
         self.collected -= 10;
-        if self.level < 29 {
+        if self.level < 29 && !self.fixed_level {
             self.level += 1;
-            play_sound_once(sounds.get(1))
+            sounds.play(1)
         }
     }
 
@@ -98,9 +193,19 @@ impl GameData {
         self.score += score;
     }
 
+    /// Pieces in a row that cleared lines, not counting the first one
+    pub fn combo(&self) -> u32 {
+        self.streak.saturating_sub(1)
+    }
+
     pub fn inc_score(&mut self, sounds: &Sounds, n: usize) {
         let k = (self.level + 1) as u32;
         self.collected += n as u16;
+        self.lines += n as u32;
+        self.streak = match n {
+            0 => 0,
+            _ => self.streak + 1,
+        };
 
         if self.collected >= 10 {
             self.inc_level(sounds)
@@ -109,19 +214,19 @@ impl GameData {
         self.score += match n {
             0 => 0,
             1 => {
-                play_sound_once(sounds.get(5));
+                sounds.play(5);
                 40 * k
             }
             2 => {
-                play_sound_once(sounds.get(5));
+                sounds.play(5);
                 100 * k
             }
             3 => {
-                play_sound_once(sounds.get(7));
+                sounds.play(7);
                 300 * k
             }
             _ => {
-                play_sound_once(sounds.get(6));
+                sounds.play(6);
                 1200 * k
             }
         };
@@ -0,0 +1,169 @@
+use macroquad::prelude::*;
+
+use crate::{
+    constants::{cell_size, num_cols, num_rows, panel_x},
+    data::Vec2,
+    fumen::{self, Fumen},
+    grid::Grid,
+    puzzle::{Goal, Puzzle},
+    screen, storage,
+    tetromino::TType,
+};
+
+fn is_ctrl_down() -> bool {
+    is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl)
+}
+
+/// Ctrl + `key`, for the editor's copy, paste and save
+pub fn shortcut(key: KeyCode) -> bool {
+    is_ctrl_down() && is_key_pressed(key)
+}
+
+// In palette order, the number keys 1 to 8 pick them too
+pub const BRUSHES: [TType; 8] = [
+    TType::I,
+    TType::O,
+    TType::T,
+    TType::L,
+    TType::S,
+    TType::Z,
+    TType::J,
+    TType::Garbage,
+];
+
+const BRUSH_KEYS: [KeyCode; 8] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+];
+
+const PIECE_KEYS: [(KeyCode, TType); 7] = [
+    (KeyCode::I, TType::I),
+    (KeyCode::O, TType::O),
+    (KeyCode::T, TType::T),
+    (KeyCode::L, TType::L),
+    (KeyCode::S, TType::S),
+    (KeyCode::Z, TType::Z),
+    (KeyCode::J, TType::J),
+];
+
+pub struct Editor {
+    pub grid: Grid,
+    pub brush: TType,
+    pub queue: Vec<TType>,
+    goal_index: usize,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self {
+            grid: Grid::from_values(vec![vec![None; num_cols()]; num_rows()]),
+            brush: TType::I,
+            queue: Vec::new(),
+            goal_index: 0,
+        }
+    }
+}
+
+impl Editor {
+    pub fn goal(&self) -> Goal {
+        Goal::PRESETS[self.goal_index]
+    }
+
+    /// Where the palette swatch for `BRUSHES[n]` is drawn, two rows of four
+    pub fn swatch(n: usize) -> Rect {
+        Rect::new(
+            panel_x() + 5. + (n % 4) as f32 * 32.,
+            80. + (n / 4) as f32 * 32.,
+            26.,
+            26.,
+        )
+    }
+
+    fn hovered_cell(&self) -> Option<Vec2> {
+        let (x, y) = screen::mouse_position();
+        let pos = Vec2::new(
+            ((y - 11.) / cell_size()).floor(),
+            ((x - 11.) / cell_size()).floor(),
+        );
+        // Only the visible rows can be painted
+        (pos.row >= 0. && self.grid.is_cell_contained(&pos)).then_some(pos)
+    }
+
+    /// Left mouse button paints with the brush and right erases, the keyboard picks the brush and
+    /// edits the queue and goal
+    pub fn update(&mut self) {
+        if let Some(pos) = self.hovered_cell() {
+            if is_mouse_button_down(MouseButton::Left) {
+                self.grid.set_type(&pos, self.brush);
+            } else if is_mouse_button_down(MouseButton::Right) {
+                self.grid.clear_cell(&pos);
+            }
+        }
+        if is_mouse_button_pressed(MouseButton::Left) {
+            let (x, y) = screen::mouse_position();
+            if let Some(n) = (0..BRUSHES.len()).find(|&n| Self::swatch(n).contains(vec2(x, y))) {
+                self.brush = BRUSHES[n];
+            }
+        }
+        for (key, brush) in BRUSH_KEYS.into_iter().zip(BRUSHES) {
+            if is_key_pressed(key) {
+                self.brush = brush;
+            }
+        }
+
+        // Ctrl+S saves, so don't add an S piece for it
+        if !is_ctrl_down() {
+            for (key, piece) in PIECE_KEYS {
+                if is_key_pressed(key) {
+                    self.queue.push(piece);
+                }
+            }
+        }
+        if is_key_pressed(KeyCode::Backspace) {
+            self.queue.pop();
+        }
+        if is_key_pressed(KeyCode::G) {
+            self.goal_index = (self.goal_index + 1) % Goal::PRESETS.len();
+        }
+        if is_key_pressed(KeyCode::Delete) {
+            self.grid = Grid::from_values(vec![vec![None; num_cols()]; num_rows()]);
+        }
+    }
+
+    pub fn load(&mut self, fumen: Fumen) {
+        self.grid = Grid::from_values(fumen.board);
+        self.queue = fumen.queue;
+    }
+
+    pub fn to_fumen(&self) -> Result<String, String> {
+        fumen::encode_board(self.grid.values(), &self.queue)
+    }
+
+    /// Writes the position as the next free `~/.tetrs/puzzles/custom-N.txt`, returning the
+    /// puzzle so it can be played straight away
+    pub fn save(&self) -> Result<Puzzle, String> {
+        if self.queue.is_empty() {
+            return Err("ADD SOME PIECES FIRST".to_owned());
+        }
+        let dir = storage::data_dir().join("puzzles");
+        let n = (1..)
+            .find(|n| !dir.join(format!("custom-{n}.txt")).exists())
+            .unwrap();
+        let puzzle = Puzzle {
+            name: format!("Custom {n}"),
+            goal: self.goal(),
+            pieces: self.queue.clone(),
+            board: self.grid.values().to_vec(),
+        };
+        match storage::write(&format!("puzzles/custom-{n}.txt"), &puzzle.to_sheet()) {
+            true => Ok(puzzle),
+            false => Err("COULD NOT SAVE".to_owned()),
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! Post-processing effects the game is drawn through on its way to the window. The built-in ones
+//! are in `resources/shaders`, and every `.glsl` fragment shader in `~/.tetrs/shaders` is added to
+//! them, replacing the built-in one with the same name. All of them are given the same uniforms,
+//! `Curvature`, `Scanlines`, `Vignette` and `Intensity` as floats and `Resolution` as the size of
+//! the game in pixels, and `~/.tetrs/effects.txt` picks the effect and tunes them:
+//!
+//! ```text
+//! effect = crt
+//! curvature = 1.0
+//! scanlines = 1.0
+//! vignette = 1.0
+//! intensity = 1.0
+//! ```
+//!
+//! Curvature, scanlines and vignette are how much the CRT screen bulges, how strong its scanlines
+//! are and how dark its corners get, and intensity is the strength of the bloom glow and the LCD
+//! grid. All of them go from 0 for none to 1 for the default.
+
+use std::fs;
+
+use macroquad::prelude::*;
+
+use crate::{
+    constants::{window_width, VERTEX_SHADER, WINDOW_HEIGHT},
+    storage,
+};
+
+const SETTINGS: &str = "effects.txt";
+
+const BUILTIN_EFFECTS: [(&str, &str); 4] = [
+    ("crt", include_str!("../resources/shaders/crt.glsl")),
+    ("bloom", include_str!("../resources/shaders/bloom.glsl")),
+    ("lcd", include_str!("../resources/shaders/lcd.glsl")),
+    ("plain", include_str!("../resources/shaders/plain.glsl")),
+];
+
+// The settings that are passed on as float uniforms, with the names of their uniforms
+const PARAMS: [(&str, &str); 4] = [
+    ("curvature", "Curvature"),
+    ("scanlines", "Scanlines"),
+    ("vignette", "Vignette"),
+    ("intensity", "Intensity"),
+];
+
+fn compile(name: &str, fragment: &str) -> Option<Material> {
+    let mut uniforms = PARAMS
+        .map(|(_, uniform)| UniformDesc::new(uniform, UniformType::Float1))
+        .to_vec();
+    uniforms.push(UniformDesc::new("Resolution", UniformType::Float2));
+    load_material(
+        ShaderSource::Glsl {
+            vertex: VERTEX_SHADER,
+            fragment,
+        },
+        MaterialParams {
+            uniforms,
+            ..Default::default()
+        },
+    )
+    .map_err(|err| eprintln!("Skipping effect {name}: {err}"))
+    .ok()
+}
+
+/// The built-in shaders with the ones in `~/.tetrs/shaders` added in or replacing them
+fn sources() -> Vec<(String, String)> {
+    let mut sources = BUILTIN_EFFECTS
+        .map(|(name, source)| (name.to_owned(), source.to_owned()))
+        .to_vec();
+    let Ok(entries) = fs::read_dir(storage::data_dir().join("shaders")) else {
+        return sources;
+    };
+    let mut paths = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "glsl"))
+        .collect::<Vec<_>>();
+    paths.sort();
+    for path in paths {
+        let (Some(name), Ok(source)) = (path.file_stem(), fs::read_to_string(&path)) else {
+            continue;
+        };
+        let name = name.to_string_lossy().to_lowercase();
+        match sources.iter_mut().find(|(builtin, _)| *builtin == name) {
+            Some(existing) => existing.1 = source,
+            None => sources.push((name, source)),
+        }
+    }
+    sources
+}
+
+pub struct Effects {
+    effects: Vec<(String, Material)>,
+    current: usize,
+    // In the order of `PARAMS`
+    params: [f32; 4],
+}
+
+impl Effects {
+    /// Compiles every effect and picks the one saved in `~/.tetrs/effects.txt`, CRT otherwise
+    pub fn load() -> Self {
+        let effects = sources()
+            .into_iter()
+            .filter_map(|(name, source)| compile(&name, &source).map(|material| (name, material)))
+            .collect();
+        let mut loaded = Self {
+            effects,
+            current: 0,
+            params: [1.; 4],
+        };
+        for (key, value) in storage::parse_pairs(&storage::read(SETTINGS).unwrap_or_default()) {
+            if key == "effect" {
+                if let Err(err) = loaded.select(value) {
+                    eprintln!("{err}");
+                }
+            } else if let Some(n) = PARAMS.iter().position(|(param, _)| *param == key) {
+                loaded.params[n] = value.parse().unwrap_or(1.);
+            }
+        }
+        loaded
+    }
+
+    pub fn select(&mut self, name: &str) -> Result<(), String> {
+        let names = self.effects.iter().map(|(name, _)| name.as_str());
+        self.current = names.clone().position(|n| n == name).ok_or_else(|| {
+            let names = names.collect::<Vec<_>>();
+            format!(
+                "no effect called `{name}`, pick one of {}",
+                names.join(", ")
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Switches to the next effect and remembers it for next time
+    pub fn cycle(&mut self) {
+        self.current = (self.current + 1) % self.effects.len().max(1);
+        self.save();
+    }
+
+    fn save(&self) {
+        let Some((name, _)) = self.effects.get(self.current) else {
+            return;
+        };
+        let mut settings = format!("effect = {name}\n");
+        for ((param, _), value) in PARAMS.iter().zip(self.params) {
+            settings.push_str(&format!("{param} = {value}\n"));
+        }
+        storage::write(SETTINGS, &settings);
+    }
+
+    /// Draws everything through the current effect until `gl_use_default_material`
+    pub fn apply(&self) {
+        let Some((_, material)) = self.effects.get(self.current) else {
+            return;
+        };
+        for ((_, uniform), value) in PARAMS.iter().zip(self.params) {
+            material.set_uniform(uniform, value);
+        }
+        material.set_uniform(
+            "Resolution",
+            vec2(window_width() as f32, WINDOW_HEIGHT as f32),
+        );
+        gl_use_material(material);
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::VecDeque;
+
+use crate::{constants::num_cols, data::Vec2, tetromino::Tetromino};
+
+#[derive(Default, Clone)]
+pub struct Finesse {
+    // Moves and rotations pressed for the piece currently falling
+    pub inputs: u32,
+    pub faults: u32,
+    pub placements: u32,
+    // (inputs used, minimal inputs) for the last faulty placement
+    pub last_fault: Option<(u32, u32)>,
+}
+
+impl Finesse {
+    pub fn input(&mut self) {
+        self.inputs += 1;
+    }
+
+    /// Scores a placement against the shortest input sequence, returns whether it was a fault
+    pub fn locked(&mut self, piece: &Tetromino) -> bool {
+        let minimal = minimal_inputs(piece);
+        let fault = self.inputs > minimal;
+        self.placements += 1;
+        if fault {
+            self.faults += 1;
+            self.last_fault = Some((self.inputs, minimal));
+        }
+        self.inputs = 0;
+        fault
+    }
+
+    pub fn fault_rate(&self) -> f32 {
+        match self.placements {
+            0 => 0.,
+            n => self.faults as f32 / n as f32,
+        }
+    }
+}
+
+/// The columns and relative heights a piece covers, which is all that matters once it lands.
+/// Rotations of symmetrical pieces (O, I, S, Z) that cover the same cells share a footprint.
+fn footprint(piece: &Tetromino) -> [(i32, i32); 4] {
+    let cells = piece.get_curr_positions();
+    let top = cells.iter().map(|c| c.row).fold(f32::MAX, f32::min);
+    let mut footprint = cells.map(|c| ((c.row - top) as i32, c.col as i32));
+    footprint.sort();
+    footprint
+}
+
+/// Fewest left/right/rotate presses that take a freshly spawned piece of the same type to the
+/// footprint of `placed`, searched over an empty board where only the walls get in the way
+pub fn minimal_inputs(placed: &Tetromino) -> u32 {
+    let target = footprint(placed);
+    let spawn = Tetromino::new(placed._type);
+    let fits = |piece: &Tetromino| {
+        piece
+            .get_curr_positions()
+            .iter()
+            .all(|c| (0.0..num_cols() as f32).contains(&c.col))
+    };
+
+    // Rotation and column offset fully describe a state when rows are ignored
+    let mut seen = vec![[false; 4]; num_cols() + 8];
+    let index = |piece: &Tetromino| (piece.offset.col + 4.) as usize;
+    let mut queue = VecDeque::from([(spawn, 0)]);
+    seen[index(&spawn)][spawn.rotation as usize] = true;
+
+    while let Some((piece, distance)) = queue.pop_front() {
+        if footprint(&piece) == target {
+            return distance;
+        }
+        let mut rotated = piece;
+        rotated.rotate();
+        let mut left = piece;
+        left.move_pos(Vec2::new(0., -1.));
+        let mut right = piece;
+        right.move_pos(Vec2::new(0., 1.));
+
+        for next in [rotated, left, right] {
+            let (col, rotation) = (index(&next), next.rotation as usize);
+            if fits(&next) && !seen[col][rotation] {
+                seen[col][rotation] = true;
+                queue.push_back((next, distance + 1));
+            }
+        }
+    }
+    // Unreachable for any piece that actually landed on the board
+    u32::MAX
+}
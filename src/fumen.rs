@@ -0,0 +1,617 @@
+//! Reading and writing fumen (v115) strings, the board diagram format used by most community
+//! tools. A fumen is a list of pages, each holding a 10 wide field of 23 rows plus a garbage row,
+//! an optional piece and an optional comment, all packed into base64 digits. Only boards that fit
+//! in that field can be shared this way.
+
+use crate::{
+    constants::{num_cols, num_rows},
+    grid::{GridValues, Row},
+    tetromino::{TType, Tetromino},
+};
+
+const PREFIX: &str = "v115@";
+const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+// Every printable ASCII character, comments are stored as indices into this
+const COMMENT_TABLE: &str =
+    " !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+
+const FIELD_ROWS: usize = 23;
+const FIELD_COLS: usize = 10;
+// The field rows plus the garbage row under them
+const FIELD_BLOCKS: usize = (FIELD_ROWS + 1) * FIELD_COLS;
+
+/// Rows of the fumen field above the top of our grid
+fn hidden_rows() -> usize {
+    FIELD_ROWS - num_rows()
+}
+
+/// Fumen fields are always 10 wide, so other boards can't be shared as one
+pub fn check_board_size() -> Result<(), String> {
+    match num_cols() == FIELD_COLS && num_rows() <= FIELD_ROWS {
+        true => Ok(()),
+        false => Err(format!(
+            "fumen only fits boards {FIELD_COLS} wide and up to {FIELD_ROWS} high"
+        )),
+    }
+}
+
+type Field = [u8; FIELD_BLOCKS];
+
+/// One page to encode: the board as it is before `piece` locks into it
+pub struct Page {
+    pub board: GridValues,
+    pub piece: Option<Tetromino>,
+    pub comment: Option<String>,
+}
+
+/// What the game can use out of a fumen: the board of its first page and the pieces to play
+#[derive(Debug)]
+pub struct Fumen {
+    pub board: GridValues,
+    pub queue: Vec<TType>,
+}
+
+fn to_fumen_type(ty: TType) -> u8 {
+    match ty {
+        TType::I => 1,
+        TType::L => 2,
+        TType::O => 3,
+        TType::Z => 4,
+        TType::T => 5,
+        TType::J => 6,
+        TType::S => 7,
+        TType::Garbage => 8,
+    }
+}
+
+fn from_fumen_type(value: u8) -> Option<TType> {
+    match value {
+        1 => Some(TType::I),
+        2 => Some(TType::L),
+        3 => Some(TType::O),
+        4 => Some(TType::Z),
+        5 => Some(TType::T),
+        6 => Some(TType::J),
+        7 => Some(TType::S),
+        8 => Some(TType::Garbage),
+        _ => None,
+    }
+}
+
+fn to_field(board: &GridValues) -> Field {
+    let mut field = [0; FIELD_BLOCKS];
+    for (row, values) in board.iter().enumerate() {
+        for (col, value) in values.iter().enumerate() {
+            field[(row + hidden_rows()) * FIELD_COLS + col] = value.map_or(0, to_fumen_type);
+        }
+    }
+    field
+}
+
+/// Anything above the top of our grid or in the garbage row is dropped
+fn from_field(field: &Field) -> GridValues {
+    field[hidden_rows() * FIELD_COLS..FIELD_ROWS * FIELD_COLS]
+        .chunks(FIELD_COLS)
+        .map(|row| row.iter().map(|&value| from_fumen_type(value)).collect())
+        .collect()
+}
+
+/// Fumen rotations are numbered reverse, right, spawn, left
+const ROTATIONS: [u32; 4] = [2, 1, 0, 3];
+
+/// Cells around the rotation centre of a spawn state piece, x to the right and y up
+fn spawn_offsets(ty: TType) -> [(i32, i32); 4] {
+    match ty {
+        TType::I => [(0, 0), (-1, 0), (1, 0), (2, 0)],
+        TType::T => [(0, 0), (-1, 0), (1, 0), (0, 1)],
+        TType::O => [(0, 0), (1, 0), (0, 1), (1, 1)],
+        TType::L => [(0, 0), (-1, 0), (1, 0), (1, 1)],
+        TType::J => [(0, 0), (-1, 0), (1, 0), (-1, 1)],
+        TType::S => [(0, 0), (-1, 0), (0, 1), (1, 1)],
+        TType::Z => [(0, 0), (1, 0), (0, 1), (-1, 1)],
+        TType::Garbage => unreachable!("garbage is never a falling piece"),
+    }
+}
+
+/// Fumen position of a piece: its rotation centre, shifted the way the original fumen placed
+/// O, I, S and Z pieces, counted from the top left of the field
+fn encode_position(piece: &Tetromino) -> u32 {
+    // Our rotation states follow spawn, right, reverse, left
+    let rotation = piece.rotation as usize;
+    let cells = piece
+        .get_curr_positions()
+        .map(|c| (c.col as i32, (num_rows() - 1) as i32 - c.row as i32));
+    let offsets = spawn_offsets(piece._type).map(|(x, y)| match rotation {
+        0 => (x, y),
+        1 => (y, -x),
+        2 => (-x, -y),
+        _ => (-y, x),
+    });
+    let (mut x, mut y) = cells
+        .into_iter()
+        .find(|&(cx, cy)| {
+            offsets
+                .iter()
+                .all(|&(dx, dy)| cells.contains(&(cx + dx, cy + dy)))
+        })
+        .unwrap();
+
+    match (piece._type, rotation) {
+        (TType::O, 3) => (x, y) = (x + 1, y - 1),
+        (TType::O, 2) | (TType::I, 2) => x += 1,
+        (TType::O, 0) | (TType::S, 0) | (TType::Z, 0) | (TType::I, 3) => y -= 1,
+        (TType::S, 1) => x -= 1,
+        (TType::Z, 3) => x += 1,
+        _ => (),
+    }
+    (FIELD_ROWS as i32 - y - 1) as u32 * FIELD_COLS as u32 + x as u32
+}
+
+fn push(data: &mut Vec<u8>, mut value: u32, digits: usize) {
+    for _ in 0..digits {
+        data.push(ALPHABET[(value % 64) as usize]);
+        value /= 64;
+    }
+}
+
+/// JavaScript's `escape`, which fumen runs comments through before storing them
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            'A'..='Z' | 'a'..='z' | '0'..='9' | '@' | '*' | '_' | '+' | '-' | '.' | '/' => {
+                escaped.push(c)
+            }
+            c if (c as u32) < 0x100 => escaped.push_str(&format!("%{:02X}", c as u32)),
+            c => {
+                for unit in c.encode_utf16(&mut [0; 2]) {
+                    escaped.push_str(&format!("%u{unit:04X}"));
+                }
+            }
+        }
+    }
+    escaped
+}
+
+/// Comments only ever hold ASCII once escaped, so this can work on bytes
+fn unescape(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let hex = |range: std::ops::Range<usize>| {
+        text.get(range)
+            .and_then(|digits| u16::from_str_radix(digits, 16).ok())
+    };
+    let mut units = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = match (bytes[i], bytes.get(i + 1)) {
+            (b'%', Some(b'u')) => hex(i + 2..i + 6).map(|unit| (unit, 6)),
+            (b'%', _) => hex(i + 1..i + 3).map(|unit| (unit, 3)),
+            _ => None,
+        };
+        let (unit, len) = escaped.unwrap_or((bytes[i] as u16, 1));
+        units.push(unit);
+        i += len;
+    }
+    String::from_utf16_lossy(&units)
+}
+
+/// Packs `pages` into a fumen string. Each piece is locked into its page's board, and the next
+/// page's board is stored as the difference from that.
+pub fn encode(pages: &[Page]) -> Result<String, String> {
+    check_board_size()?;
+    let mut data = Vec::new();
+    let mut previous = [0; FIELD_BLOCKS];
+    // Index in `data` of the count of pages repeating an unchanged field
+    let mut repeat: Option<usize> = None;
+
+    for page in pages {
+        let too_high = |piece: &Tetromino| {
+            let top = -(hidden_rows() as f32);
+            piece.get_curr_positions().iter().any(|cell| cell.row < top)
+        };
+        if page.piece.as_ref().is_some_and(too_high) {
+            return Err("a piece is above the top of the fumen field".to_owned());
+        }
+        let field = to_field(&page.board);
+        if field == previous && repeat.is_some_and(|at| data[at] != ALPHABET[63]) {
+            let at = repeat.unwrap();
+            let count = ALPHABET.iter().position(|&c| c == data[at]).unwrap();
+            data[at] = ALPHABET[count + 1];
+        } else {
+            let mut runs: Vec<(u32, u32)> = Vec::new();
+            for (current, before) in field.iter().zip(previous) {
+                let diff = (*current as u32 + 8) - before as u32;
+                match runs.last_mut() {
+                    Some((last, count)) if *last == diff => *count += 1,
+                    _ => runs.push((diff, 1)),
+                }
+            }
+            for &(diff, count) in &runs {
+                push(&mut data, diff * FIELD_BLOCKS as u32 + count - 1, 2);
+            }
+            repeat = None;
+            if field == previous {
+                repeat = Some(data.len());
+                push(&mut data, 0, 1);
+            }
+        }
+
+        // Flags from the most significant: not locked, comment, guideline colours, mirror, rise
+        let comment = page.comment.as_deref().map(escape);
+        let mut action = 0b00100 | (comment.is_some() as u32) << 3;
+        action *= FIELD_BLOCKS as u32;
+        match page.piece {
+            Some(piece) => {
+                action += encode_position(&piece);
+                action = action * 4 + ROTATIONS[piece.rotation as usize];
+                action = action * 8 + to_fumen_type(piece._type) as u32;
+            }
+            // No piece, fumen puts these at the bottom left of the hidden rows
+            None => action *= 4 * 8,
+        }
+        push(&mut data, action, 3);
+
+        if let Some(comment) = comment {
+            let comment = comment.chars().take(4095).collect::<Vec<_>>();
+            push(&mut data, comment.len() as u32, 2);
+            for chunk in comment.chunks(4) {
+                let value = chunk.iter().rev().fold(0, |value, &c| {
+                    value * 96 + COMMENT_TABLE.find(c).unwrap() as u32
+                });
+                push(&mut data, value, 5);
+            }
+        }
+
+        previous = field;
+        if let Some(piece) = page.piece {
+            lock(&mut previous, &piece);
+        }
+    }
+
+    // Fumen breaks the data up with a `?` after the first 42 characters and every 47 after that
+    let data = String::from_utf8(data).unwrap();
+    let mut encoded = String::from(PREFIX);
+    encoded.push_str(&data[..data.len().min(42)]);
+    for chunk in data.as_bytes().get(42..).unwrap_or(&[]).chunks(47) {
+        encoded.push('?');
+        encoded.push_str(std::str::from_utf8(chunk).unwrap());
+    }
+    Ok(encoded)
+}
+
+/// Places a piece into the field and clears the lines it completes, like fumen does between pages
+fn lock(field: &mut Field, piece: &Tetromino) {
+    for cell in piece.get_curr_positions() {
+        let index = (cell.row + hidden_rows() as f32) as usize * FIELD_COLS + cell.col as usize;
+        field[index] = to_fumen_type(piece._type);
+    }
+    let rows = field[..FIELD_ROWS * FIELD_COLS]
+        .chunks(FIELD_COLS)
+        .filter(|row| row.contains(&0))
+        .flatten()
+        .copied()
+        .collect::<Vec<_>>();
+    let cleared = FIELD_ROWS * FIELD_COLS - rows.len();
+    field[..cleared].fill(0);
+    field[cleared..FIELD_ROWS * FIELD_COLS].copy_from_slice(&rows);
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn poll(&mut self, digits: usize) -> Result<u32, String> {
+        if self.data.len() < digits {
+            return Err("fumen data ends too early".to_owned());
+        }
+        let mut value = 0;
+        for (n, c) in self.data[..digits].iter().enumerate() {
+            let digit = ALPHABET
+                .iter()
+                .position(|a| a == c)
+                .ok_or_else(|| format!("invalid fumen character `{}`", *c as char))?;
+            value += (digit as u32) << (6 * n);
+        }
+        self.data = &self.data[digits..];
+        Ok(value)
+    }
+}
+
+/// Reads a `v115@...` string, or a fumen link containing one. Pieces come from a `#Q=` quiz
+/// comment on the first page when there is one, or else from the piece on every page.
+pub fn decode(text: &str) -> Result<Fumen, String> {
+    check_board_size()?;
+    let start = text
+        .find(PREFIX)
+        .ok_or("only v115 fumen strings are supported")?;
+    let data = text[start + PREFIX.len()..]
+        .chars()
+        .filter(|&c| c != '?')
+        .take_while(|c| !c.is_whitespace() && *c != '#' && *c != '&')
+        .collect::<String>();
+    let mut reader = Reader {
+        data: data.as_bytes(),
+    };
+
+    let mut board = None;
+    let mut quiz = None;
+    let mut pieces = Vec::new();
+    let mut repeat = 0;
+    let mut page = 0;
+    while !reader.data.is_empty() {
+        if repeat > 0 {
+            repeat -= 1;
+        } else {
+            let mut field = [0; FIELD_BLOCKS];
+            let mut filled = 0;
+            while filled < FIELD_BLOCKS {
+                let value = reader.poll(2)?;
+                let (diff, count) = (
+                    value / FIELD_BLOCKS as u32,
+                    (value % FIELD_BLOCKS as u32) as usize + 1,
+                );
+                if diff > 16 || filled + count > FIELD_BLOCKS {
+                    return Err("invalid fumen field".to_owned());
+                }
+                // Later pages are stored relative to the page before, only their pieces matter
+                field[filled..filled + count].fill((diff as i32 - 8).max(0) as u8);
+                if count == FIELD_BLOCKS && diff == 8 {
+                    repeat = reader.poll(1)?;
+                }
+                filled += count;
+            }
+            board.get_or_insert_with(|| from_field(&field));
+        }
+
+        let action = reader.poll(3)?;
+        if let Some(piece) = from_fumen_type((action % 8) as u8).filter(|&p| p != TType::Garbage) {
+            pieces.push(piece);
+        }
+        let has_comment = (action / (8 * 4 * FIELD_BLOCKS as u32)) & 0b01000 != 0;
+        if has_comment {
+            let len = reader.poll(2)? as usize;
+            let mut comment = String::new();
+            for _ in 0..len.div_ceil(4) {
+                let mut value = reader.poll(5)?;
+                for _ in 0..4 {
+                    comment.push(COMMENT_TABLE.as_bytes()[(value % 96) as usize % 95] as char);
+                    value /= 96;
+                }
+            }
+            comment.truncate(len);
+            if page == 0 {
+                quiz = Some(unescape(&comment));
+            }
+        }
+        page += 1;
+    }
+
+    let board = board.ok_or("fumen has no pages")?;
+    let queue = match quiz.as_deref().and_then(|c| c.strip_prefix("#Q=")) {
+        Some(quiz) => parse_quiz(quiz)?,
+        None => pieces,
+    };
+    Ok(Fumen { board, queue })
+}
+
+/// `[H](C)NNN` is the held piece, the current piece and the next ones. There is no hold here, so
+/// the held piece is dealt after the others.
+fn parse_quiz(quiz: &str) -> Result<Vec<TType>, String> {
+    let invalid = || format!("invalid quiz `{quiz}`");
+    let (hold, rest) = quiz
+        .strip_prefix('[')
+        .and_then(|q| q.split_once(']'))
+        .ok_or_else(invalid)?;
+    let (current, next) = rest
+        .strip_prefix('(')
+        .and_then(|q| q.split_once(')'))
+        .ok_or_else(invalid)?;
+    current
+        .chars()
+        .chain(next.chars().take_while(|c| !c.is_whitespace() && *c != ';'))
+        .chain(hold.chars())
+        .map(|c| match TType::from_char(c) {
+            Some(TType::Garbage) | None => Err(invalid()),
+            Some(piece) => Ok(piece),
+        })
+        .collect()
+}
+
+/// A single page with `board` and the pieces to play as a quiz comment
+pub fn encode_board(board: &[Row], queue: &[TType]) -> Result<String, String> {
+    let comment = queue.split_first().map(|(current, next)| {
+        format!(
+            "#Q=[]({}){}",
+            current.to_char(),
+            next.iter().map(|p| p.to_char()).collect::<String>()
+        )
+    });
+    encode(&[Page {
+        board: board.to_vec(),
+        piece: None,
+        comment,
+    }])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Vec2;
+
+    // Four rows of six garbage cells at the bottom, the example in the README
+    const README_EXAMPLE: &str = "v115@9gF8DeF8DeF8DeF8NeAgH";
+    // An I and a T lying flat on the bottom row from the fourth column, as fumen writes them
+    const I_ON_THE_FLOOR: &str = "v115@vhARQJ";
+    const T_ON_THE_FLOOR: &str = "v115@vhAVQJ";
+
+    fn empty_board() -> GridValues {
+        vec![vec![None; num_cols()]; num_rows()]
+    }
+
+    fn piece(ty: TType, rotation: usize, row: f32, col: f32) -> Tetromino {
+        let mut piece = Tetromino::new(ty);
+        for _ in 0..rotation {
+            piece.rotate();
+        }
+        piece.offset = Vec2::new(row, col);
+        piece
+    }
+
+    fn on_the_floor(ty: TType) -> Page {
+        Page {
+            board: empty_board(),
+            piece: Some(piece(ty, 0, num_rows() as f32 - 2., 3.)),
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn decodes_an_empty_field() {
+        let fumen = decode("v115@vhAAgH").unwrap();
+        assert_eq!(fumen.board, empty_board());
+        assert!(fumen.queue.is_empty());
+    }
+
+    #[test]
+    fn decodes_the_readme_example() {
+        let fumen = decode(README_EXAMPLE).unwrap();
+        let mut board = empty_board();
+        for row in &mut board[num_rows() - 4..] {
+            row[..6].fill(Some(TType::Garbage));
+        }
+        assert_eq!(fumen.board, board);
+        assert!(fumen.queue.is_empty());
+    }
+
+    #[test]
+    fn decodes_links_and_split_data() {
+        let link = format!("https://fumen.zui.jp/?{README_EXAMPLE}#english");
+        assert_eq!(
+            decode(&link).unwrap().board,
+            decode(README_EXAMPLE).unwrap().board
+        );
+        let split = "v115@9gF8DeF8De?F8DeF8NeAgH";
+        assert_eq!(
+            decode(split).unwrap().board,
+            decode(README_EXAMPLE).unwrap().board
+        );
+    }
+
+    #[test]
+    fn decodes_the_piece_of_a_page() {
+        let fumen = decode(I_ON_THE_FLOOR).unwrap();
+        assert_eq!(fumen.board, empty_board());
+        assert_eq!(fumen.queue, vec![TType::I]);
+        assert_eq!(decode(T_ON_THE_FLOOR).unwrap().queue, vec![TType::T]);
+    }
+
+    #[test]
+    fn rejects_invalid_data() {
+        assert!(decode("v110@vhAAgH").is_err());
+        assert!(decode("v115@vh").is_err());
+        assert!(decode("v115@vh!AgH").is_err());
+        assert!(decode("v115@").is_err());
+    }
+
+    #[test]
+    fn encodes_an_empty_field() {
+        let page = Page {
+            board: empty_board(),
+            piece: None,
+            comment: None,
+        };
+        assert_eq!(encode(&[page]).unwrap(), "v115@vhAAgH");
+    }
+
+    #[test]
+    fn encodes_the_readme_example() {
+        let mut board = empty_board();
+        for row in &mut board[num_rows() - 4..] {
+            row[..6].fill(Some(TType::Garbage));
+        }
+        assert_eq!(encode_board(&board, &[]).unwrap(), README_EXAMPLE);
+    }
+
+    #[test]
+    fn encodes_pieces_like_fumen() {
+        assert_eq!(encode(&[on_the_floor(TType::I)]).unwrap(), I_ON_THE_FLOOR);
+        assert_eq!(encode(&[on_the_floor(TType::T)]).unwrap(), T_ON_THE_FLOOR);
+    }
+
+    #[test]
+    fn round_trips_a_quiz_comment() {
+        let mut board = empty_board();
+        board[num_rows() - 1][..9].fill(Some(TType::Garbage));
+        board[num_rows() - 2][..3].fill(Some(TType::S));
+        let queue = vec![TType::T, TType::I, TType::O, TType::L];
+        let encoded = encode_board(&board, &queue).unwrap();
+
+        let fumen = decode(&encoded).unwrap();
+        assert_eq!(fumen.board, board);
+        assert_eq!(fumen.queue, queue);
+        assert_eq!(encode_board(&fumen.board, &fumen.queue).unwrap(), encoded);
+    }
+
+    #[test]
+    fn round_trips_several_pages() {
+        let mut board = empty_board();
+        board[num_rows() - 1][..6].fill(Some(TType::Garbage));
+        let pages = [
+            // Clears the bottom row, so the next page starts from an empty field again
+            Page {
+                board: board.clone(),
+                piece: Some(piece(TType::I, 0, num_rows() as f32 - 2., 6.)),
+                comment: None,
+            },
+            Page {
+                board: empty_board(),
+                piece: Some(piece(TType::T, 2, num_rows() as f32 - 3., 0.)),
+                comment: Some("next: T".to_owned()),
+            },
+            Page {
+                board: empty_board(),
+                piece: Some(piece(TType::Z, 1, num_rows() as f32 - 3., 4.)),
+                comment: None,
+            },
+        ];
+        let fumen = decode(&encode(&pages).unwrap()).unwrap();
+        assert_eq!(fumen.board, board);
+        assert_eq!(fumen.queue, vec![TType::I, TType::T, TType::Z]);
+    }
+
+    #[test]
+    fn splits_long_data() {
+        let queue = [TType::PIECES; 4].concat();
+        let encoded = encode_board(&empty_board(), &queue).unwrap();
+        assert_eq!(encoded.as_bytes()[PREFIX.len() + 42], b'?');
+        assert_eq!(decode(&encoded).unwrap().queue, queue);
+    }
+
+    #[test]
+    fn escapes_comments_like_javascript() {
+        assert_eq!(escape("#Q=[](T)"), "%23Q%3D%5B%5D%28T%29");
+        assert_eq!(escape("é✓"), "%E9%u2713");
+        for text in ["#Q=[](T)IO", "spaces and 100%", "é✓ unicode"] {
+            assert_eq!(unescape(&escape(text)), text);
+        }
+    }
+
+    #[test]
+    fn deals_the_held_piece_last() {
+        let queue = parse_quiz("[S](T)IO;#Q=").unwrap();
+        assert_eq!(queue, vec![TType::T, TType::I, TType::O, TType::S]);
+        assert!(parse_quiz("(T)IO").is_err());
+        assert!(parse_quiz("[](X)").is_err());
+    }
+
+    #[test]
+    fn refuses_pieces_above_the_field() {
+        let page = Page {
+            board: empty_board(),
+            piece: Some(piece(TType::O, 0, -(hidden_rows() as f32) - 1., 4.)),
+            comment: None,
+        };
+        assert!(encode(&[page]).is_err());
+    }
+}
@@ -1,245 +1,1080 @@
 use macroquad::{
-    audio::{play_sound, play_sound_once, set_sound_volume, PlaySoundParams},
+    audio::{play_sound, set_sound_volume, PlaySoundParams},
+    miniquad::window::{clipboard_get, clipboard_set},
     prelude::*,
 };
 use macroquad_text::Fonts;
 
 use crate::{
-    animator::AnimationRegulator,
+    ai::{self, Autopilot},
+    animator::{Anim, AnimationRegulator, Ease},
+    bot::Bot,
     constants::*,
-    data::{GameData, GameState, Vec2},
+    data::{GameData, GameMode, GameState},
+    editor::{self, Editor, BRUSHES},
+    effects::Effects,
+    finesse::Finesse,
+    fumen::{self, Fumen, Page},
     grid::Grid,
+    history::{History, Snapshot},
+    hud::{self, Placement, Widget},
+    net::{Connection, Lobby},
+    opener::Opener,
+    player::{Controls, Player},
+    puzzle::Puzzle,
+    screen,
     selector::Selector,
+    settings::{self, Setting, FLASH, PARTICLES, PATTERNS, SHAKE, SMOOTH},
     sounds::Sounds,
-    tetromino::{TType, Tetromino},
+    stream::{self, StreamServer},
+    tetromino::TType,
+    theme::{draw_block, theme},
+    versus::{self, Versus},
 };
 
+// The keys that switch the settings on and off, named as the menu lists them
+const TOGGLES: [(KeyCode, &str, &Setting); 5] = [
+    (KeyCode::F4, "F4", &PARTICLES),
+    (KeyCode::F5, "F5", &SMOOTH),
+    (KeyCode::F6, "F6", &SHAKE),
+    (KeyCode::F7, "F7", &FLASH),
+    (KeyCode::F8, "F8", &PATTERNS),
+];
+
 pub struct Game<'a> {
-    selector: Selector,
-    grid: Grid,
+    player: Player,
     state: GameState,
     fonts: Fonts<'a>,
-    data: GameData,
     sounds: Sounds,
-    has_current_changed: bool,
     animation_handler: AnimationRegulator,
+    mode: GameMode,
+    menu_index: usize,
+    zen_level: u8,
+    puzzles: Vec<Puzzle>,
+    puzzle_index: usize,
+    puzzle_lines: usize,
+    finesse: Finesse,
+    openers: Vec<Opener>,
+    opener_index: usize,
+    opener_placed: usize,
+    history: History,
+    editor: Editor,
+    message: String,
+    versus: Versus,
+    lobby: Option<Lobby>,
+    stream: Option<StreamServer>,
+    bot: Option<Bot>,
+    // Set while the AI plays a demo game from the title screen
+    demo: Option<Autopilot>,
+    idle_since: f64,
+    hints: bool,
+    fullscreen: bool,
+    effects: Effects,
+    // How far the screen shakes at the start of the current shake
+    shake: f32,
+    hud: Vec<Placement>,
+    started_at: f64,
+    // Set once the game is over, so its clock stops
+    ended_at: Option<f64>,
 }
 
 impl Game<'_> {
     pub async fn new() -> Self {
+        settings::load();
         let mut fonts = Fonts::default();
-        fonts.load_font_from_bytes("Main", FONT).unwrap();
+        fonts.load_font_from_bytes("Main", theme().font()).unwrap();
         Self {
             fonts,
-            has_current_changed: false,
             sounds: Sounds::new().await,
-            selector: Selector::default(),
-            grid: Grid::default(),
+            player: Player::default(),
             state: GameState::default(),
-            data: GameData::default(),
             animation_handler: AnimationRegulator::default(),
+            mode: GameMode::default(),
+            menu_index: 0,
+            zen_level: 5,
+            puzzles: Puzzle::load_all(),
+            puzzle_index: 0,
+            puzzle_lines: 0,
+            finesse: Finesse::default(),
+            openers: Opener::load_all(),
+            opener_index: 0,
+            opener_placed: 0,
+            history: History::default(),
+            editor: Editor::default(),
+            message: String::new(),
+            versus: Versus::default(),
+            lobby: None,
+            stream: None,
+            bot: None,
+            demo: None,
+            idle_since: get_time(),
+            hints: false,
+            fullscreen: false,
+            effects: Effects::load(),
+            shake: 0.,
+            hud: hud::load(),
+            started_at: 0.,
+            ended_at: None,
         }
     }
 
-    pub fn is_inside(&self, block: &Tetromino) -> bool {
-        block
-            .get_curr_positions()
-            .iter()
-            .all(|tile| self.grid.is_cell_contained(tile) && self.grid.is_empty(tile))
+    pub fn start(&mut self, mode: GameMode) {
+        self.mode = mode;
+        let (grid, selector) = match mode {
+            GameMode::Puzzle => {
+                let puzzle = &self.puzzles[self.puzzle_index];
+                (
+                    Grid::from_values(puzzle.board.clone()),
+                    Selector::with_sequence(&puzzle.pieces),
+                )
+            }
+            GameMode::Opener => {
+                let opener = &self.openers[self.opener_index];
+                (
+                    Grid::from_values(vec![vec![None; num_cols()]; num_rows()]),
+                    Selector::with_sequence(&opener.pieces),
+                )
+            }
+            GameMode::Editor => (
+                self.editor.grid.clone(),
+                Selector::with_sequence(&self.editor.queue),
+            ),
+            _ => (Grid::default(), Selector::default()),
+        };
+        let data = match mode {
+            GameMode::Zen => GameData::zen(self.zen_level),
+            _ => GameData::default(),
+        };
+        self.player = Player::new(grid, selector, data);
+        self.player.grid.invisible = mode == GameMode::Invisible;
+        if mode == GameMode::Versus {
+            self.versus = Versus::default();
+        }
+        self.puzzle_lines = 0;
+        self.opener_placed = 0;
+        self.started_at = get_time();
+        self.ended_at = None;
+        self.history.clear();
+        self.finesse = Finesse::default();
+        self.animation_handler = AnimationRegulator::default();
+        self.animation_handler.start(Anim::Ghost, 0.4);
+        if self.player.grid.invisible {
+            self.player.grid.reveal(&mut self.animation_handler);
+        }
+        if let Some(bot) = &mut self.bot {
+            bot.next_piece();
+        }
+        self.demo = None;
+        self.state = GameState::Playing;
+    }
+
+    fn start_demo(&mut self) {
+        self.start(GameMode::Classic);
+        self.demo = Some(Autopilot::default());
+    }
+
+    fn stop_demo(&mut self) {
+        self.demo = None;
+        self.state = GameState::Menu;
+        self.idle_since = get_time();
+    }
+
+    /// Draws through the effect called `name` for this run, without saving it
+    pub fn use_effect(&mut self, name: &str) -> Result<(), String> {
+        self.effects.select(name)
     }
 
-    pub fn is_block_inside(&self) -> bool {
-        self.is_inside(&self.selector.current)
+    /// Hands the controls of single player games over to `bot`, starting with a classic game
+    pub fn play_with(&mut self, bot: Bot) {
+        self.bot = Some(bot);
+        self.start(GameMode::Classic);
     }
 
-    pub fn is_ghost_inside(&self) -> bool {
-        self.is_inside(&self.selector.ghost)
+    fn bot_failed(&mut self, err: String) {
+        eprintln!("Bot stopped playing: {err}");
+        self.bot = None;
+        self.show_message(err.to_uppercase());
     }
 
     pub fn lock_block(&mut self) {
-        let ty = self.selector.current._type;
-        for tile in self.selector.current.get_curr_positions() {
-            self.grid.set_type(&tile, ty)
+        if let Some(bot) = &mut self.bot {
+            bot.next_piece();
         }
-        self.selector.block_locked();
-        if !self.is_block_inside() {
-            self.state = GameState::GameOver;
+        let piece = self.player.selector.current;
+        let tspin = self.player.is_tspin();
+        if self.mode == GameMode::Opener && !self.openers[self.opener_index].fits(&piece) {
+            // Wrong spot, rewind to just before this piece was placed
+            self.player.respawn();
             self.animation_handler
-                .reset_animation(0, GAMEOVER_ANIM_DURATION);
-            play_sound_once(self.sounds.get(0));
-            set_sound_volume(self.sounds.get(0), 0.5);
+                .start(Anim::OpenerMistake, OPENER_MISTAKE_ANIM_DURATION);
+            self.sounds.play(0);
+            return;
+        }
+        if self.mode.is_practice() {
+            self.history.push(Snapshot {
+                grid: self.player.grid.clone(),
+                selector: self.player.selector.clone(),
+                data: self.player.data.clone(),
+                finesse: self.finesse.clone(),
+                puzzle_lines: self.puzzle_lines,
+                opener_placed: self.opener_placed,
+            });
+        }
+        let locked_out = self.player.is_locked_out();
+        self.player.place();
+        if self.player.grid.invisible {
+            for tile in piece.get_curr_positions() {
+                self.player
+                    .grid
+                    .start_fade(&tile, &mut self.animation_handler);
+            }
+        }
+        if self.mode == GameMode::Finesse && self.finesse.locked(&piece) {
+            self.animation_handler
+                .start(Anim::FinesseFault, FINESSE_FAULT_ANIM_DURATION);
+        }
+
+        self.player.grid.land(&piece, &self.animation_handler);
+        let cleared = self.player.grid.check_complete(&mut self.animation_handler);
+        self.player.data.inc_score(&self.sounds, cleared);
+        self.player.data.attack += versus::attack(cleared, tspin, self.player.grid.is_clear());
+        self.celebrate(cleared);
+        if cleared > 0 && self.mode == GameMode::Zen {
+            self.player.data.save_zen();
+        }
+
+        let spawned = self.player.spawn_next();
+
+        if self.mode == GameMode::Puzzle {
+            let puzzle = &self.puzzles[self.puzzle_index];
+            self.puzzle_lines += cleared;
+            if puzzle.goal.is_met(
+                self.puzzle_lines,
+                cleared,
+                tspin,
+                self.player.grid.is_clear(),
+            ) {
+                return self.finish(GameState::Solved);
+            }
+            if self.player.data.pieces as usize >= puzzle.pieces.len() {
+                return self.finish(GameState::GameOver);
+            }
+        }
+
+        if self.mode == GameMode::Opener {
+            self.opener_placed += 1;
+            if self.opener_placed == self.openers[self.opener_index].pieces.len() {
+                return self.finish(GameState::Solved);
+            }
+        }
+
+        let topped_out = locked_out || !spawned;
+        if topped_out && self.mode == GameMode::Zen {
+            // Zen mode never ends, topping out just wipes the upper half of the stack
+            self.player.grid.clear_top(num_rows() / 2);
+            if !self.player.respawn() {
+                self.player.grid.clear_top(num_rows());
+                self.player.respawn();
+            }
+            self.sounds.play(1);
+        } else if topped_out {
+            self.finish(GameState::GameOver);
+        } else {
+            self.sounds.play(4);
+        }
+    }
+
+    /// Steps back to right before the last piece locked, with that piece back at the top
+    pub fn undo(&mut self) {
+        let Some(snapshot) = self.history.pop() else {
+            return;
+        };
+        let mut grid = snapshot.grid;
+        grid.take_animations(&mut self.player.grid);
+        self.player.grid = grid;
+        self.player.selector = snapshot.selector;
+        self.player.respawn();
+        self.player.data = snapshot.data;
+        self.finesse = snapshot.finesse;
+        self.finesse.inputs = 0;
+        self.puzzle_lines = snapshot.puzzle_lines;
+        self.opener_placed = snapshot.opener_placed;
+        self.ended_at = None;
+
+        if let Some(bot) = &mut self.bot {
+            bot.next_piece();
+        }
+        self.state = GameState::Playing;
+        self.sounds.play(2);
+    }
+
+    /// Leaving a game goes back to the editor when it was started from there
+    fn leave(&mut self) {
+        if self.mode == GameMode::Zen {
+            self.player.data.save_zen();
+        }
+        self.state = match self.mode {
+            GameMode::Editor => GameState::Editing,
+            _ => GameState::Menu,
+        };
+    }
+
+    fn show_message(&mut self, message: String) {
+        self.message = message;
+        self.animation_handler
+            .start(Anim::Message, MESSAGE_ANIM_DURATION);
+    }
+
+    fn save_editor(&mut self) {
+        let message = match self.editor.save() {
+            Ok(puzzle) => {
+                let message = format!("SAVED AS {}", puzzle.name.to_uppercase());
+                self.puzzles.push(puzzle);
+                message
+            }
+            Err(err) => err,
+        };
+        self.show_message(message);
+    }
+
+    /// Opens the editor on an imported position
+    pub fn open_fumen(&mut self, fumen: Fumen) {
+        self.editor.load(fumen);
+        self.mode = GameMode::Editor;
+        self.state = GameState::Editing;
+    }
+
+    pub fn stream_to(&mut self, server: StreamServer) {
+        self.stream = Some(server);
+    }
+
+    fn broadcast(&mut self) {
+        if self.stream.is_none() {
+            return;
+        }
+        let players = match self.mode {
+            GameMode::Versus => &self.versus.players[..],
+            _ => std::slice::from_ref(&self.player),
+        };
+        let frame = stream::frame(&self.state, self.mode, players);
+        if let Some(server) = &mut self.stream {
+            server.broadcast(&frame);
+        }
+    }
+
+    /// Waits in the lobby until the other player is there, then starts an online versus game
+    pub fn connect(&mut self, lobby: Lobby) {
+        self.lobby = Some(lobby);
+        self.mode = GameMode::Versus;
+        self.state = GameState::Connecting;
+    }
+
+    fn update_lobby(&mut self) {
+        let Some(lobby) = &mut self.lobby else {
+            return;
+        };
+        match lobby.poll() {
+            Ok(Some((connection, seed))) => {
+                self.lobby = None;
+                self.start_online(connection, seed);
+            }
+            Ok(None) => (),
+            Err(err) => {
+                eprintln!("Could not start an online game: {err}");
+                self.lobby = None;
+                self.state = GameState::Menu;
+                self.show_message(err.to_uppercase());
+            }
+        }
+    }
+
+    fn start_online(&mut self, connection: Connection, seed: u64) {
+        self.start(GameMode::Versus);
+        self.versus = Versus::online(connection, seed);
+    }
+
+    fn paste_fumen(&mut self) {
+        match fumen::decode(&clipboard_get().unwrap_or_default()) {
+            Ok(fumen) => {
+                self.open_fumen(fumen);
+                self.show_message("PASTED FUMEN".to_owned());
+            }
+            Err(err) => {
+                eprintln!("Could not read fumen from the clipboard: {err}");
+                self.show_message("NO FUMEN TO PASTE".to_owned());
+            }
+        }
+    }
+
+    /// Copies to the clipboard, and prints it too for when there is no clipboard to copy to
+    fn copy_fumen(&mut self, fumen: Result<String, String>) {
+        let fumen = match fumen {
+            Ok(fumen) => fumen,
+            Err(err) => return self.show_message(err.to_uppercase()),
+        };
+        clipboard_set(&fumen);
+        println!("{fumen}");
+        self.show_message("COPIED AS FUMEN".to_owned());
+    }
+
+    /// One page for every placement still in the undo history, then the board as it is now
+    fn game_fumen(&self) -> Result<String, String> {
+        let mut pages = self
+            .history
+            .snapshots()
+            .iter()
+            .map(|snapshot| Page {
+                board: snapshot.grid.values().to_vec(),
+                piece: Some(snapshot.selector.current),
+                comment: None,
+            })
+            .collect::<Vec<_>>();
+        pages.push(Page {
+            board: self.player.grid.values().to_vec(),
+            piece: None,
+            comment: None,
+        });
+        fumen::encode(&pages)
+    }
+
+    fn finish(&mut self, state: GameState) {
+        self.ended_at = Some(get_time());
+        self.animation_handler
+            .start(Anim::GameOver, GAMEOVER_ANIM_DURATION);
+        if state == GameState::Solved {
+            self.sounds.play(1);
         } else {
-            play_sound_once(self.sounds.get(4));
-            self.has_current_changed = true;
+            self.sounds.play(0);
+            set_sound_volume(self.sounds.get(0), 0.5);
+        }
+        self.state = state;
+        if self.mode == GameMode::Versus {
+            return;
+        }
+        if let Some(Err(err)) = self.bot.as_mut().map(|bot| bot.game_over(&self.player)) {
+            self.bot_failed(err);
         }
     }
 
     pub async fn draw_game_ui(&self) {
-        self.fonts.draw_text("LEVEL:", 350., 20.0, 50, *GOLD_SAND);
-        draw_rectangle(340., 80., 130., 40., *CRATER_BROWN);
+        for place in &self.hud {
+            let data = &self.player.data;
+            let minutes = self.elapsed() / 60.;
+            match place.widget {
+                Widget::Level => self.draw_stat(place, "LEVEL:", &data.level.to_string()),
+                Widget::Score => self.draw_stat(place, "SCORE:", &data.score.to_string()),
+                Widget::Lines => self.draw_stat(place, "LINES:", &data.lines.to_string()),
+                Widget::Time => {
+                    let seconds = self.elapsed();
+                    let time = format!("{}:{:04.1}", (seconds / 60.) as u32, seconds % 60.);
+                    self.draw_stat(place, "TIME:", &time)
+                }
+                Widget::Pps => {
+                    let pps = data.pieces as f64 / (minutes * 60.).max(1.);
+                    self.draw_stat(place, "PPS:", &format!("{pps:.2}"))
+                }
+                Widget::Apm => {
+                    let apm = data.attack as f64 / minutes.max(1. / 60.);
+                    self.draw_stat(place, "APM:", &format!("{apm:.1}"))
+                }
+                Widget::Combo => self.draw_stat(place, "COMBO:", &data.combo().to_string()),
+                Widget::Next => self.draw_next_widget(place).await,
+                Widget::Stats => self.draw_piece_stats(place),
+                Widget::Mode => self.draw_mode_info(place),
+            }
+        }
+    }
+
+    /// Seconds since the game started, up to when it ended
+    fn elapsed(&self) -> f64 {
+        self.ended_at.unwrap_or_else(get_time) - self.started_at
+    }
+
+    /// A label with its value in a box under it
+    fn draw_stat(&self, place: &Placement, label: &str, value: &str) {
+        let (x, y, k) = (panel_x() + place.x, place.y, place.scale);
         self.fonts
-            .draw_text(&format!("{}", self.data.level), 350., 80.0, 32, *ZOMBIE);
+            .draw_text(label, x + 10. * k, y, (50. * k) as u16, theme().text);
+        draw_rectangle(x, y + 60. * k, 130. * k, 40. * k, theme().cell);
+        self.fonts.draw_text(
+            value,
+            x + 10. * k,
+            y + 60. * k,
+            (32. * k) as u16,
+            theme().value,
+        );
+    }
 
-        self.fonts.draw_text("SCORE:", 350., 140.0, 50, *GOLD_SAND);
-        draw_rectangle(340., 200., 130., 40., *CRATER_BROWN);
+    async fn draw_next_widget(&self, place: &Placement) {
+        let (x, y, k) = (panel_x() + place.x, place.y, place.scale);
         self.fonts
-            .draw_text(&format!("{}", self.data.score), 350., 200.0, 32, *ZOMBIE);
+            .draw_text("NEXT:", x + 10. * k, y, (50. * k) as u16, theme().text);
+        draw_rectangle(x, y + 60. * k, 130. * k, 140. * k, theme().cell);
+        // Past the end of a puzzle the selector deals random pieces, don't show those
+        if self.mode == GameMode::Puzzle {
+            let puzzle = &self.puzzles[self.puzzle_index];
+            let left = puzzle
+                .pieces
+                .len()
+                .saturating_sub(self.player.data.pieces as usize);
+            if left < 2 {
+                return;
+            }
+        }
+        self.player.draw_next(x, y + 60. * k, k).await
+    }
 
-        self.fonts.draw_text("NEXT:", 350., 280.0, 50, *GOLD_SAND);
-        draw_rectangle(340., 340., 130., 140., *CRATER_BROWN);
+    /// How many of each piece were placed, one piece to a line
+    fn draw_piece_stats(&self, place: &Placement) {
+        let (x, y, k) = (panel_x() + place.x, place.y, place.scale);
+        self.fonts
+            .draw_text("PIECES:", x + 10. * k, y, (50. * k) as u16, theme().text);
+        for (n, ty) in TType::PIECES.into_iter().enumerate() {
+            let line = y + 60. * k + n as f32 * 22. * k;
+            draw_block(x + 10. * k, line + 2. * k, 16. * k, ty, ty.into());
+            self.fonts.draw_text(
+                &self.player.data.placed[n].to_string(),
+                x + 36. * k,
+                line,
+                (20. * k) as u16,
+                theme().value,
+            );
+        }
+    }
 
-        self.selector
-            .next
-            .draw_with_offset(match self.selector.next._type {
-                TType::I => Vec2::new(395., 255.),
-                TType::O => Vec2::new(380., 255.),
-                _s => Vec2::new(380., 270.0),
-            })
-            .await
+    /// The goal of a puzzle, the progress of an opener or the faults in finesse
+    fn draw_mode_info(&self, place: &Placement) {
+        let lines = match self.mode {
+            GameMode::Puzzle => {
+                let puzzle = &self.puzzles[self.puzzle_index];
+                let remaining = puzzle
+                    .pieces
+                    .len()
+                    .saturating_sub(self.player.data.pieces as usize);
+                [puzzle.goal.describe(), format!("PIECES LEFT: {remaining}")]
+            }
+            GameMode::Opener => {
+                let opener = &self.openers[self.opener_index];
+                [
+                    opener.name.to_uppercase(),
+                    format!("PLACED: {}/{}", self.opener_placed, opener.pieces.len()),
+                ]
+            }
+            GameMode::Finesse => [
+                format!("FAULTS: {}", self.finesse.faults),
+                format!("INPUTS: {}", self.finesse.inputs),
+            ],
+            _ => return,
+        };
+        let (x, y, k) = (panel_x() + place.x, place.y, place.scale);
+        let size = (20. * k) as u16;
+        self.fonts.draw_text(&lines[0], x, y, size, theme().text);
+        self.fonts
+            .draw_text(&lines[1], x, y + 30. * k, size, theme().value);
+    }
+
+    fn draw_centered_text(&self, text: &str, y: f32, size: u16, colour: Color) {
+        let width = self.fonts.measure_text(text, size).width;
+        self.fonts
+            .draw_text(text, (window_width() as f32 - width) / 2., y, size, colour);
+    }
+
+    /// Whether `mode` needs a puzzle or opener and none of them fit the board
+    fn nothing_to_play(&self, mode: GameMode) -> bool {
+        match mode {
+            GameMode::Puzzle => self.puzzles.is_empty(),
+            GameMode::Opener => self.openers.is_empty(),
+            _ => false,
+        }
+    }
+
+    pub async fn draw_menu(&self) {
+        self.draw_centered_text("TETRS", 30., 120, theme().accent);
+        for (n, mode) in GameMode::ALL.iter().enumerate() {
+            let colour = match n == self.menu_index {
+                true => theme().text,
+                false => theme().dim,
+            };
+            let label = match mode {
+                GameMode::Zen => format!("{} < {} >", mode.name(), self.zen_level),
+                GameMode::Puzzle => format!("{} < {} >", mode.name(), self.puzzle_index + 1),
+                GameMode::Opener => format!("{} < {} >", mode.name(), self.opener_index + 1),
+                _ => mode.name().to_owned(),
+            };
+            self.draw_centered_text(&label, 180. + n as f32 * 40., 36, colour);
+        }
+        let selected = GameMode::ALL[self.menu_index];
+        if self.nothing_to_play(selected) {
+            self.draw_centered_text(
+                &format!("NONE FIT A {}X{} BOARD", num_cols(), num_rows()),
+                540.,
+                20,
+                theme().text,
+            );
+        } else if selected == GameMode::Puzzle {
+            let puzzle = &self.puzzles[self.puzzle_index];
+            self.draw_centered_text(
+                &format!("{}: {}", puzzle.name.to_uppercase(), puzzle.goal.describe()),
+                540.,
+                20,
+                theme().text,
+            );
+        }
+        if selected == GameMode::Opener && !self.openers.is_empty() {
+            let opener = &self.openers[self.opener_index];
+            self.draw_centered_text(&opener.name.to_uppercase(), 540., 20, theme().text);
+        }
+        if GameMode::ALL[self.menu_index] == GameMode::Versus {
+            self.draw_centered_text("P1: [W][A][S][D]   P2: ARROW KEYS", 540., 20, theme().text);
+        }
+        if GameMode::ALL[self.menu_index] == GameMode::Editor {
+            self.draw_centered_text(
+                "PAINT A BOARD, THEN PLAY OR SAVE IT",
+                540.,
+                20,
+                theme().text,
+            );
+        }
+        self.draw_centered_text(
+            "[UP]/[DOWN] to choose, [ENTER] to play!",
+            580.,
+            20,
+            theme().value,
+        );
+        self.draw_toggles(605.);
+    }
+
+    /// The keys for the settings in a row, lit up for the ones that are on
+    fn draw_toggles(&self, y: f32) {
+        let gap = 14.;
+        let labels =
+            TOGGLES.map(|(_, key, setting)| format!("{key} {}", setting.name.to_uppercase()));
+        let widths = labels
+            .iter()
+            .map(|label| self.fonts.measure_text(label, 14).width)
+            .collect::<Vec<_>>();
+        let total = widths.iter().sum::<f32>() + gap * (labels.len() - 1) as f32;
+        let mut x = (window_width() as f32 - total) / 2.;
+        for ((label, width), (_, _, setting)) in labels.iter().zip(widths).zip(TOGGLES) {
+            let colour = match setting.is_on() {
+                true => theme().value,
+                false => theme().dim,
+            };
+            self.fonts.draw_text(label, x, y, 14, colour);
+            x += width + gap;
+        }
+    }
+
+    fn draw_lobby(&self) {
+        self.draw_centered_text("VERSUS", 30., 120, theme().accent);
+        if let Some(lobby) = &self.lobby {
+            self.draw_centered_text(&lobby.status, 280., 36, theme().text);
+        }
+        self.draw_centered_text("Press [ESC] to cancel", 580., 20, theme().value);
+    }
+
+    /// A message across the top of the board that fades out as `anim` plays
+    fn draw_banner(&self, text: &str, anim: Anim) {
+        if !self.animation_handler.is_running(anim) {
+            return;
+        }
+        let alpha = 1. - self.animation_handler.eased(anim, Ease::In) as f32;
+        let (mut colour, mut back) = (theme().accent, theme().shadow);
+        colour.a = alpha;
+        back.a = alpha * 0.8;
+
+        let board_width = cell_size() * num_cols() as f32;
+        let width = self.fonts.measure_text(text, 20).width;
+        draw_rectangle(11., 40., board_width - 1., 40., back);
+        self.fonts
+            .draw_text(text, 11. + (board_width - width) / 2., 48., 20, colour);
+    }
+
+    pub async fn draw_editor(&self) {
+        self.editor.grid.draw(&self.animation_handler).await;
+
+        self.fonts
+            .draw_text("BRUSH:", panel_x() + 10., 20., 50, theme().text);
+        for (n, brush) in BRUSHES.iter().enumerate() {
+            let swatch = Editor::swatch(n);
+            draw_block(swatch.x, swatch.y, swatch.w, *brush, (*brush).into());
+            if *brush == self.editor.brush {
+                draw_rectangle_lines(
+                    swatch.x - 3.,
+                    swatch.y - 3.,
+                    swatch.w + 6.,
+                    swatch.h + 6.,
+                    3.,
+                    theme().value,
+                );
+            }
+        }
+
+        self.fonts
+            .draw_text("QUEUE:", panel_x() + 10., 150., 50, theme().text);
+        draw_rectangle(panel_x(), 210., 130., 80., theme().cell);
+        // Only the first 30 pieces fit, ten to a line
+        for (n, line) in self.editor.queue.chunks(10).take(3).enumerate() {
+            let text = line.iter().map(|p| p.to_char()).collect::<String>();
+            self.fonts.draw_text(
+                &text,
+                panel_x() + 5.,
+                212. + n as f32 * 25.,
+                20,
+                theme().value,
+            );
+        }
+
+        self.fonts
+            .draw_text("GOAL:", panel_x() + 10., 300., 50, theme().text);
+        draw_rectangle(panel_x(), 360., 130., 40., theme().cell);
+        self.fonts.draw_text(
+            &self.editor.goal().describe(),
+            panel_x() + 5.,
+            368.,
+            16,
+            theme().value,
+        );
+
+        for (n, hint) in [
+            "[1-8] BRUSH",
+            "[RIGHT CLICK] ERASE",
+            "[IOTLSZJ] ADD PIECE",
+            "[BACKSPACE] REMOVE",
+            "[G] NEXT GOAL",
+            "[DEL] CLEAR BOARD",
+            "[ENTER] PLAY",
+            "[CTRL+S] SAVE",
+            "[CTRL+C/V] FUMEN",
+        ]
+        .iter()
+        .enumerate()
+        {
+            self.fonts
+                .draw_text(hint, panel_x(), 415. + n as f32 * 22., 16, theme().dim);
+        }
+
+        self.draw_banner(&self.message, Anim::Message);
     }
 
     pub async fn draw_game_over(&self) {
         draw_rectangle(
             0.,
             0.,
-            WINDOW_WIDTH as f32,
+            window_width() as f32,
             WINDOW_HEIGHT as f32,
-            self.animation_handler.colour_map(0, *EERIE_BLACK),
+            self.animation_handler
+                .fade_in(Anim::GameOver, theme().shadow, Ease::InOut),
         );
 
-        self.fonts.draw_text(
-            "GAME OVER",
-            (WINDOW_WIDTH as f32 - 380.) / 2.,
-            150.,
+        let title = match (&self.state, self.mode) {
+            (GameState::Solved, GameMode::Opener) => "BUILT!",
+            (GameState::Solved, _) => "SOLVED!",
+            (_, GameMode::Puzzle) => "FAILED",
+            (_, GameMode::Versus) if self.versus.disconnected.is_some() => "DISCONNECTED",
+            (_, GameMode::Versus) if self.versus.is_online() && self.versus.winner == Some(0) => {
+                "YOU WIN!"
+            }
+            (_, GameMode::Versus) if self.versus.is_online() => "YOU LOSE",
+            (_, GameMode::Versus) if self.versus.winner == Some(0) => "P1 WINS!",
+            (_, GameMode::Versus) => "P2 WINS!",
+            _ => "GAME OVER",
+        };
+        // The title drops in from the top, bouncing where it lands or springing in when solved
+        let ease = match self.state {
+            GameState::Solved => Ease::Elastic,
+            _ => Ease::Bounce,
+        };
+        let drop = self.animation_handler.eased(Anim::GameOver, ease) as f32;
+        self.draw_centered_text(
+            title,
+            150. * drop,
             100,
-            self.animation_handler.colour_map(0, *BURNT_SIENNA1),
-        );
-        let zombie = self.animation_handler.colour_map(0, *ZOMBIE);
-        self.fonts
-            .draw_text("LEVEL", (WINDOW_WIDTH as f32 - 380.) / 2., 250., 60, zombie);
-        self.fonts.draw_text(
-            &format!(": {}", self.data.level),
-            ((WINDOW_WIDTH as f32 - 380.) / 2.) + 140.,
-            250.,
-            60,
-            zombie,
-        );
-        self.fonts
-            .draw_text("SCORE", (WINDOW_WIDTH as f32 - 380.) / 2., 310., 60, zombie);
-        self.fonts.draw_text(
-            &format!(": {}", self.data.score),
-            ((WINDOW_WIDTH as f32 - 380.) / 2.) + 140.,
-            310.,
-            60,
-            zombie,
+            self.animation_handler
+                .fade_in(Anim::GameOver, theme().accent, Ease::Out),
         );
-        self.fonts.draw_text(
-            "Press [R] to retry!",
-            (WINDOW_WIDTH as f32 - 132.5) / 2.,
-            400.,
+        let zombie = self
+            .animation_handler
+            .fade_in(Anim::GameOver, theme().value, Ease::Out);
+        if self.mode == GameMode::Versus {
+            for (n, player) in self.versus.players.iter().enumerate() {
+                self.draw_centered_text(
+                    &format!(
+                        "{}: {} LINES, {} SENT",
+                        self.versus.name(n),
+                        player.data.lines,
+                        self.versus.sent[n]
+                    ),
+                    250. + n as f32 * 60.,
+                    36,
+                    zombie,
+                );
+            }
+        } else {
+            self.fonts.draw_text(
+                "LEVEL",
+                (window_width() as f32 - 380.) / 2.,
+                250.,
+                60,
+                zombie,
+            );
+            self.fonts.draw_text(
+                &format!(": {}", self.player.data.level),
+                ((window_width() as f32 - 380.) / 2.) + 140.,
+                250.,
+                60,
+                zombie,
+            );
+            self.fonts.draw_text(
+                "SCORE",
+                (window_width() as f32 - 380.) / 2.,
+                310.,
+                60,
+                zombie,
+            );
+            self.fonts.draw_text(
+                &format!(": {}", self.player.data.score),
+                ((window_width() as f32 - 380.) / 2.) + 140.,
+                310.,
+                60,
+                zombie,
+            );
+        }
+        if let (GameMode::Versus, Some(reason)) = (self.mode, &self.versus.disconnected) {
+            self.draw_centered_text(&reason.to_uppercase(), 365., 24, zombie);
+        }
+        if self.mode == GameMode::Finesse {
+            self.draw_centered_text(
+                &format!(
+                    "FAULTS: {} / {} PIECES ({:.0}%)",
+                    self.finesse.faults,
+                    self.finesse.placements,
+                    self.finesse.fault_rate() * 100.
+                ),
+                365.,
+                24,
+                zombie,
+            );
+        }
+        // An online game cannot be replayed without connecting again
+        if !self.versus.is_online() || self.mode != GameMode::Versus {
+            self.fonts.draw_text(
+                "Press [R] to retry!",
+                (window_width() as f32 - 132.5) / 2.,
+                400.,
+                20,
+                self.animation_handler
+                    .blink_colour(Anim::GameOver, theme().dim),
+            );
+        }
+        self.draw_centered_text(
+            "Press [ESC] for the menu",
+            430.,
             20,
             self.animation_handler
-                .colour_blink_map(0, *crate::constants::BROWN),
+                .blink_colour(Anim::GameOver, theme().dim),
         );
+        if self.mode.is_practice() {
+            self.draw_centered_text(
+                "Press [Z] to undo the last piece",
+                460.,
+                20,
+                self.animation_handler
+                    .blink_colour(Anim::GameOver, theme().dim),
+            );
+            self.draw_centered_text(
+                "Press [CTRL+C] to copy as fumen",
+                490.,
+                20,
+                self.animation_handler
+                    .blink_colour(Anim::GameOver, theme().dim),
+            );
+        }
     }
 
-    pub async fn draw(&mut self) {
+    /// Shakes the screen for cleared lines, and flashes the board on a Tetris
+    fn celebrate(&mut self, cleared: usize) {
+        if cleared > 0 && SHAKE.is_on() {
+            self.shake = cleared as f32 * SHAKE_PER_LINE;
+            self.animation_handler
+                .start(Anim::Shake, SHAKE_ANIM_DURATION);
+        }
+        if cleared == 4 && FLASH.is_on() {
+            self.animation_handler
+                .start(Anim::TetrisFlash, TETRIS_FLASH_ANIM_DURATION);
+        }
+    }
+
+    /// Where the camera is pushed this frame by the shake, fading out as it ends
+    fn shake_offset(&self) -> Vec2 {
+        let strength =
+            self.shake * (1. - self.animation_handler.eased(Anim::Shake, Ease::Out) as f32);
+        let t = get_time() as f32;
+        vec2((t * 73.).sin(), (t * 91.).cos()) * strength
+    }
+
+    pub async fn draw(&mut self, camera: &Camera2D) {
         // print!("\x1B[2J\x1B[1;1H");
-        // println!("{:?}", self.grid);
+        // println!("{:?}", self.player.grid);
 
-        self.grid.draw().await;
-        self.selector.current.draw().await;
+        if self.state == GameState::Menu {
+            self.draw_menu().await;
+            return;
+        }
+        if self.state == GameState::Editing {
+            self.draw_editor().await;
+            return;
+        }
+        if self.state == GameState::Connecting {
+            self.draw_lobby();
+            return;
+        }
+        if self.mode == GameMode::Versus {
+            self.versus
+                .draw(&self.fonts, &mut self.animation_handler, camera)
+                .await;
+            if self.state == GameState::GameOver {
+                self.draw_game_over().await;
+            }
+            return;
+        }
 
-        self.selector
-            .ghost
-            .draw_outline(&self.animation_handler)
-            .await;
+        self.player.grid.draw(&self.animation_handler).await;
+        if self.mode == GameMode::Opener {
+            self.openers[self.opener_index].draw_overlay(&self.player.grid);
+        }
+        if self.hints && self.demo.is_none() && self.state == GameState::Playing {
+            if let Some((_, landed)) = ai::best_placement(&self.player) {
+                landed.draw_hint();
+            }
+        }
+        self.player.draw_pieces(&self.animation_handler).await;
 
         self.draw_game_ui().await;
-        self.grid
+        if self.demo.is_some() {
+            self.fonts
+                .draw_text("DEMO", panel_x() + 10., 500., 50, theme().accent);
+            self.fonts
+                .draw_text("PRESS ANY KEY", panel_x() + 10., 560., 20, theme().text);
+        }
+        if let (GameMode::Finesse, Some((used, minimal))) = (self.mode, self.finesse.last_fault) {
+            self.draw_banner(
+                &format!("FAULT: {used} INPUTS, {minimal} NEEDED"),
+                Anim::FinesseFault,
+            );
+        }
+        if self.mode == GameMode::Opener {
+            self.draw_banner("WRONG SPOT, TRY AGAIN!", Anim::OpenerMistake);
+        }
+        self.player
+            .grid
             .draw_row_collapse_animation(&mut self.animation_handler);
+        if self.animation_handler.is_running(Anim::TetrisFlash) {
+            let fade = self.animation_handler.eased(Anim::TetrisFlash, Ease::Out);
+            let alpha = (1. - fade) as f32 * 0.6;
+            draw_rectangle(
+                11.,
+                11.,
+                num_cols() as f32 * cell_size() - 1.,
+                num_rows() as f32 * cell_size() - 1.,
+                Color::new(1., 1., 1., alpha),
+            );
+        }
 
-        if self.state == GameState::GameOver {
+        if matches!(self.state, GameState::GameOver | GameState::Solved) {
             self.draw_game_over().await;
+            self.draw_banner(&self.message, Anim::Message);
         }
     }
 
-    pub async fn update(&mut self) {
-        if is_key_pressed(KeyCode::Right) {
-            self.selector.current.move_pos(Vec2::new(0., 1.));
-            if !self.is_block_inside() {
-                self.selector.current.move_pos(Vec2::new(0., -1.));
-            } else {
-                play_sound_once(self.sounds.get(3))
-            }
+    pub fn update_menu(&mut self) {
+        if get_last_key_pressed().is_some() {
+            self.idle_since = get_time();
         }
-        if is_key_pressed(KeyCode::Left) {
-            self.selector.current.move_pos(Vec2::new(0., -1.));
-            if !self.is_block_inside() {
-                self.selector.current.move_pos(Vec2::new(0., 1.));
-            } else {
-                play_sound_once(self.sounds.get(3))
-            }
+        if get_time() - self.idle_since > ATTRACT_DELAY {
+            return self.start_demo();
+        }
+        let count = GameMode::ALL.len();
+        if is_key_pressed(KeyCode::Down) {
+            self.menu_index = (self.menu_index + 1) % count;
+            self.sounds.play(3)
+        }
+        if is_key_pressed(KeyCode::Up) {
+            self.menu_index = (self.menu_index + count - 1) % count;
+            self.sounds.play(3)
         }
-        if is_key_down(KeyCode::Down) {
-            if !self.has_current_changed {
-                self.selector.current.move_pos(Vec2::new(1., 0.));
-                if !self.is_block_inside() {
-                    self.selector.current.move_pos(Vec2::new(-1., 0.));
-                    self.lock_block();
-                } else {
-                    self.data.add_to_score(1);
+        match GameMode::ALL[self.menu_index] {
+            GameMode::Zen => {
+                if is_key_pressed(KeyCode::Right) {
+                    self.zen_level = (self.zen_level + 1).min(29);
+                }
+                if is_key_pressed(KeyCode::Left) {
+                    self.zen_level = self.zen_level.saturating_sub(1);
                 }
             }
-        } else {
-            self.has_current_changed = false;
+            GameMode::Puzzle if !self.puzzles.is_empty() => {
+                let count = self.puzzles.len();
+                if is_key_pressed(KeyCode::Right) {
+                    self.puzzle_index = (self.puzzle_index + 1) % count;
+                }
+                if is_key_pressed(KeyCode::Left) {
+                    self.puzzle_index = (self.puzzle_index + count - 1) % count;
+                }
+            }
+            GameMode::Opener if !self.openers.is_empty() => {
+                let count = self.openers.len();
+                if is_key_pressed(KeyCode::Right) {
+                    self.opener_index = (self.opener_index + 1) % count;
+                }
+                if is_key_pressed(KeyCode::Left) {
+                    self.opener_index = (self.opener_index + count - 1) % count;
+                }
+            }
+            _ => (),
         }
-        if is_key_pressed(KeyCode::Up) {
-            self.selector.current.rotate();
-            if !self.is_block_inside() {
-                self.selector.current.undo_rotate();
-            } else {
-                play_sound_once(self.sounds.get(2))
+        if is_key_pressed(KeyCode::Enter) && !self.nothing_to_play(GameMode::ALL[self.menu_index]) {
+            match GameMode::ALL[self.menu_index] {
+                GameMode::Editor => {
+                    self.mode = GameMode::Editor;
+                    self.state = GameState::Editing;
+                }
+                mode => self.start(mode),
             }
         }
+    }
 
-        if self.data.has_dropped() {
-            self.selector.current.move_pos(Vec2::new(1., 0.));
-            if !self.is_block_inside() {
-                self.selector.current.move_pos(Vec2::new(-1., 0.));
-                self.lock_block();
+    pub async fn update(&mut self) {
+        if is_key_pressed(KeyCode::H) {
+            self.hints = !self.hints;
+        }
+        let input = match (&mut self.demo, &mut self.bot) {
+            (Some(demo), _) => demo.poll(&self.player),
+            (None, Some(bot)) => match bot.poll(&self.player) {
+                Ok(input) => input,
+                Err(err) => return self.bot_failed(err),
+            },
+            (None, None) => Controls::ARROWS.poll(),
+        };
+        if self.mode == GameMode::Finesse {
+            for pressed in [input.left, input.right, input.rotate] {
+                if pressed {
+                    self.finesse.input();
+                }
             }
         }
-
-        self.selector.ghost.offset = self.selector.current.offset;
-        self.selector.ghost.rotation = self.selector.current.rotation;
-        while self.is_ghost_inside() {
-            self.selector.ghost.move_pos(Vec2::new(1., 0.))
+        if self.player.update(input, &self.sounds) {
+            self.lock_block();
         }
-        self.selector.ghost.move_pos(Vec2::new(-1., 0.));
-
-        self.data.inc_score(
-            &self.sounds,
-            self.grid.check_complete(&mut self.animation_handler),
-        );
+        self.player.update_ghost();
     }
 
     pub async fn run(&mut self) {
-        self.animation_handler.reset_animation(1, 0.4);
-        let render_target = render_target(WINDOW_WIDTH as u32, WINDOW_HEIGHT as u32);
+        let render_target = render_target(window_width() as u32, WINDOW_HEIGHT as u32);
         render_target.texture.set_filter(FilterMode::Nearest);
 
-        let material = load_material(
-            ShaderSource::Glsl {
-                vertex: VERTEX_SHADER,
-                fragment: FRAGMENT_SHADER,
-            },
-            MaterialParams2::default(),
-        )
-        .unwrap();
-        let camera = Camera2D {
-            zoom: vec2(2. / WINDOW_WIDTH as f32, 2. / WINDOW_HEIGHT as f32),
-            target: vec2(screen_width() / 2., screen_height() / 2.),
+        let mut camera = Camera2D {
+            zoom: vec2(2. / window_width() as f32, 2. / WINDOW_HEIGHT as f32),
+            target: vec2(window_width() as f32 / 2., WINDOW_HEIGHT as f32 / 2.),
             render_target: Some(render_target.clone()),
             ..Default::default()
         };
@@ -253,36 +1088,101 @@ impl Game<'_> {
         );
 
         loop {
-            // set_window_size(WINDOW_WIDTH as u32, WINDOW_HEIGHT as u32);
-            let playing = self.state == GameState::Playing;
-            if playing {
-                self.update().await;
+            if is_key_pressed(KeyCode::F11) {
+                self.fullscreen = !self.fullscreen;
+                set_fullscreen(self.fullscreen);
+            }
+            if is_key_pressed(KeyCode::F9) {
+                self.effects.cycle();
+            }
+            for (key, _, setting) in TOGGLES {
+                if is_key_pressed(key) {
+                    setting.toggle();
+                }
+            }
+            if self.mode.is_practice()
+                && matches!(
+                    self.state,
+                    GameState::Playing | GameState::GameOver | GameState::Solved
+                )
+                && is_key_pressed(KeyCode::Z)
+            {
+                self.undo();
             }
 
-            // Restart
-
-            if is_key_pressed(KeyCode::R) && !playing {
-                self.selector = Selector::default();
-                self.grid = Grid::default();
-                self.data = GameData::default();
-                self.animation_handler = AnimationRegulator::default();
-                self.state = GameState::default();
+            match self.state {
+                _ if self.demo.is_some() && get_last_key_pressed().is_some() => self.stop_demo(),
+                GameState::GameOver if self.demo.is_some() => self.stop_demo(),
+                GameState::Menu => self.update_menu(),
+                GameState::Connecting if is_key_pressed(KeyCode::Escape) => {
+                    self.lobby = None;
+                    self.state = GameState::Menu;
+                }
+                GameState::Connecting => self.update_lobby(),
+                GameState::Playing if is_key_pressed(KeyCode::Escape) => self.leave(),
+                GameState::Playing if self.mode == GameMode::Versus => {
+                    if self
+                        .versus
+                        .update(&self.sounds, &mut self.animation_handler)
+                    {
+                        self.finish(GameState::GameOver);
+                    }
+                }
+                GameState::Playing => self.update().await,
+                // A bot plays game after game on its own, the next PIECE starts the new one. Versus
+                // isn't a bot's to play, that game ends on the screen saying who won.
+                GameState::GameOver | GameState::Solved
+                    if self.bot.is_some() && self.mode != GameMode::Versus =>
+                {
+                    self.start(self.mode)
+                }
+                GameState::GameOver | GameState::Solved => {
+                    // Restart
+                    if is_key_pressed(KeyCode::R)
+                        && !(self.mode == GameMode::Versus && self.versus.is_online())
+                    {
+                        self.start(self.mode);
+                    }
+                    if is_key_pressed(KeyCode::Escape) {
+                        self.leave();
+                    }
+                    if self.mode.is_practice() && editor::shortcut(KeyCode::C) {
+                        self.copy_fumen(self.game_fumen());
+                    }
+                }
+                GameState::Editing if is_key_pressed(KeyCode::Escape) => {
+                    self.state = GameState::Menu
+                }
+                GameState::Editing if is_key_pressed(KeyCode::Enter) => {
+                    self.start(GameMode::Editor)
+                }
+                GameState::Editing if editor::shortcut(KeyCode::S) => self.save_editor(),
+                GameState::Editing if editor::shortcut(KeyCode::C) => {
+                    self.copy_fumen(self.editor.to_fumen())
+                }
+                GameState::Editing if editor::shortcut(KeyCode::V) => self.paste_fumen(),
+                GameState::Editing => self.editor.update(),
             }
 
+            self.broadcast();
+
+            camera.target =
+                vec2(window_width() as f32 / 2., WINDOW_HEIGHT as f32 / 2.) + self.shake_offset();
             set_camera(&camera);
-            clear_background(*COCOA_BROWN);
-            self.draw().await;
+            clear_background(theme().background);
+            self.draw(&camera).await;
             set_default_camera();
 
-            clear_background(*COCOA_BROWN);
-            gl_use_material(&material);
+            clear_background(BLACK);
+            let view = screen::viewport();
+            self.effects.apply();
             draw_texture_ex(
                 &render_target.texture,
-                0.,
-                0.,
+                view.x,
+                view.y,
                 WHITE,
                 DrawTextureParams {
-                    dest_size: Some(vec2(WINDOW_WIDTH as f32, WINDOW_HEIGHT as f32)),
+                    dest_size: Some(view.size()),
                     ..Default::default()
                 },
             );
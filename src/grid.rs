@@ -1,26 +1,37 @@
 use std::fmt::Debug;
 
 use crate::{
-    animator::AnimationRegulator,
-    constants::{CELL_SIZE, NUM_COLS, NUM_ROWS},
+    animator::{Anim, AnimationRegulator, Ease, Sequence},
+    constants::{
+        buffer_rows, cell_size, num_cols, num_rows, CELL_FADE_DELAY, CELL_FADE_DURATION,
+        STACK_REVEAL_DURATION, STACK_REVEAL_FADE_IN,
+    },
     data::Vec2,
-    tetromino::TType,
+    particles::Particles,
+    tetromino::{TType, Tetromino},
+    theme::{draw_block, draw_hidden_block, theme},
     ROW_DISSAPEAR_ANIM_DURATION,
 };
 use macroquad::prelude::*;
 
-pub type GridValues = Vec<Vec<Option<TType>>>;
+pub type Row = Vec<Option<TType>>;
+pub type GridValues = Vec<Row>;
 
+/// The board, with `buffer_rows()` hidden rows above the visible ones. Positions count rows from
+/// the top of the visible field, so the hidden rows are at negative rows.
+#[derive(Clone)]
 pub struct Grid {
     values: GridValues,
-    collapsed: Vec<(u16, usize, Vec<Option<TType>>)>,
+    collapsed: Vec<(Anim, usize, Row)>,
+    particles: Particles,
+    pub invisible: bool,
 }
 
 impl Debug for Grid {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_fmt(format_args!(
             "╔{}╗\n",
-            (0..(NUM_COLS * 2 + 1)).map(|_| "═").collect::<String>()
+            (0..(num_cols() * 2 + 1)).map(|_| "═").collect::<String>()
         ))?;
         for i in &self.values {
             f.write_str("║ ")?;
@@ -37,7 +48,7 @@ impl Debug for Grid {
         }
         f.write_fmt(format_args!(
             "╚{}╝\n",
-            (0..(NUM_COLS * 2 + 1)).map(|_| "═").collect::<String>()
+            (0..(num_cols() * 2 + 1)).map(|_| "═").collect::<String>()
         ))
     }
 }
@@ -46,8 +57,12 @@ impl Debug for Grid {
 #[cfg(debug_assertions)]
 impl Default for Grid {
     fn default() -> Self {
-        Self {
-            values: vec![
+        use crate::constants::{DEFAULT_COLS, DEFAULT_ROWS};
+        // The filled in board for trying things out only fits the default size
+        if (num_cols(), num_rows()) != (DEFAULT_COLS, DEFAULT_ROWS) {
+            return Self::from_values(vec![vec![None; num_cols()]; num_rows()]);
+        }
+        Self::from_values(vec![
                 vec![None,None,None,None,None,None,None, None, None, None,],
                 vec![None,None,None,None,None,None,None, None, None, None,],
                 vec![None,None,None,None,None,None,None, None, None, None,],
@@ -68,43 +83,101 @@ impl Default for Grid {
                 vec![Some(TType::I),Some(TType::I),None,Some(TType::I),Some(TType::I),Some(TType::I),Some(TType::I), Some(TType::I), Some(TType::I), Some(TType::I),],
                 vec![Some(TType::I),Some(TType::I),None,Some(TType::I),Some(TType::I),Some(TType::I),Some(TType::I), Some(TType::I), Some(TType::I), Some(TType::I),],
                 vec![Some(TType::I),Some(TType::I),None,Some(TType::I),Some(TType::I),Some(TType::I),Some(TType::I), Some(TType::I), Some(TType::I), Some(TType::I),],
-                ],
-            collapsed: Vec::with_capacity(NUM_COLS),
-        }
+                ])
     }
 }
 
 #[cfg(not(debug_assertions))]
 impl Default for Grid {
     fn default() -> Self {
-        Self {
-            values: vec![vec![None; NUM_COLS]; NUM_ROWS],
-            collapsed: Vec::with_capacity(NUM_COLS),
-        }
+        Self::from_values(vec![vec![None; num_cols()]; num_rows()])
     }
 }
 
 impl Grid {
-    pub async fn draw(&self) {
-        for (row, valr) in self.values.iter().enumerate() {
+    /// A grid showing `values`, with empty hidden rows above them
+    pub fn from_values(values: GridValues) -> Self {
+        let mut all = vec![vec![None; num_cols()]; buffer_rows()];
+        all.extend(values);
+        Self {
+            values: all,
+            collapsed: Vec::with_capacity(num_cols()),
+            particles: Particles::default(),
+            invisible: false,
+        }
+    }
+
+    pub async fn draw(&self, reg: &AnimationRegulator) {
+        // Cells locked in the hidden rows still block pieces coming in, so they peek out at the top
+        if !self.invisible {
+            for (col, val) in self.values[buffer_rows() - 1].iter().enumerate() {
+                if let Some(t) = val {
+                    let x = col as f32 * cell_size() + 11.;
+                    draw_hidden_block(x, 11. - cell_size(), cell_size() - 1., (*t).into());
+                }
+            }
+        }
+        for (row, valr) in self.values().iter().enumerate() {
+            for (col, val) in valr.iter().enumerate() {
+                let (x, y) = (
+                    col as f32 * cell_size() + 11.,
+                    row as f32 * cell_size() + 11.,
+                );
+                draw_rectangle(x, y, cell_size() - 1., cell_size() - 1., theme().cell);
+                if let Some(t) = val {
+                    let mut color: Color = (*t).into();
+                    if self.invisible {
+                        color.a = reg.value(Anim::CellFade(row, col)).unwrap_or(0.) as f32;
+                    }
+                    draw_block(x, y, cell_size() - 1., *t, color)
+                }
+            }
+        }
+    }
+
+    /// Shown for `delay` seconds, then faded out
+    fn fade(delay: f64) -> Sequence {
+        Sequence::starting_at(1.)
+            .hold(delay)
+            .then(0., CELL_FADE_DURATION, Ease::Linear)
+    }
+
+    pub fn start_fade(&self, pos: &Vec2, reg: &mut AnimationRegulator) {
+        if pos.row < 0. {
+            return;
+        }
+        reg.play(
+            Anim::CellFade(pos.row as usize, pos.col as usize),
+            Self::fade(CELL_FADE_DELAY),
+        );
+    }
+
+    /// Shows every locked cell again for a moment before it fades back out
+    pub fn reveal(&self, reg: &mut AnimationRegulator) {
+        for (row, valr) in self.values().iter().enumerate() {
             for (col, val) in valr.iter().enumerate() {
-                draw_rectangle(
-                    col as f32 * CELL_SIZE + 11.,
-                    row as f32 * CELL_SIZE + 11.,
-                    CELL_SIZE - 1.,
-                    CELL_SIZE - 1.,
-                    match val {
-                        Some(t) => (*t).into(),
-                        _ => *crate::CRATER_BROWN,
-                    },
-                )
+                let anim = Anim::CellFade(row, col);
+                match val {
+                    Some(_) => reg.play(
+                        anim,
+                        Sequence::ratio(STACK_REVEAL_FADE_IN)
+                            .chain(Self::fade(STACK_REVEAL_DURATION)),
+                    ),
+                    None => reg.stop(anim),
+                }
             }
         }
     }
 
+    /// Where the row of `pos` is in `values`
+    fn index(pos: &Vec2) -> usize {
+        (pos.row + buffer_rows() as f32) as usize
+    }
+
+    /// Whether `pos` is on the board, hidden rows included
     pub fn is_cell_contained(&self, pos: &Vec2) -> bool {
-        if (0.0..(NUM_ROWS as f32)).contains(&pos.row)
-            && (0.0..(NUM_COLS as f32)).contains(&pos.col)
+        if (-(buffer_rows() as f32)..(num_rows() as f32)).contains(&pos.row)
+            && (0.0..(num_cols() as f32)).contains(&pos.col)
         {
             return true;
         }
@@ -112,7 +185,7 @@ impl Grid {
     }
 
     pub fn is_empty(&self, pos: &Vec2) -> bool {
-        self.values[pos.row as usize][pos.col as usize].is_none()
+        self.values[Self::index(pos)][pos.col as usize].is_none()
     }
 
     pub fn check_complete(&mut self, reg: &mut AnimationRegulator) -> usize {
@@ -127,29 +200,41 @@ impl Grid {
         let total = completed.len();
         for row in completed {
             let res = self.values.remove(row);
-            let id = ((get_time() * 100.) as u16 % 10000) + 2;
-            self.collapsed.push((id, row, res));
-            reg.reset_animation(id, ROW_DISSAPEAR_ANIM_DURATION);
-            self.values.insert(0, vec![None; NUM_COLS]);
+            self.values.insert(0, vec![None; num_cols()]);
+            // Rows cleared out of sight, or without a window to see them, aren't animated
+            let Some(row) = row.checked_sub(buffer_rows()) else {
+                continue;
+            };
+            if reg.is_headless() {
+                continue;
+            }
+            self.particles.sparks(row, &res, reg);
+            let anim = Anim::RowClear(reg.allocate_id());
+            self.collapsed.push((anim, row, res));
+            reg.start(anim, ROW_DISSAPEAR_ANIM_DURATION);
+        }
+        if self.invisible && total > 0 {
+            self.reveal(reg);
         }
         total
     }
 
     pub fn draw_row_collapse_animation(&mut self, reg: &mut AnimationRegulator) {
         let mut done = Vec::new();
-        for (n, (id, orig_row, collapsed)) in self.collapsed.iter().enumerate() {
-            let ratio = reg.animation_completed_ratio(*id) as f32;
+        for (n, (anim, orig_row, collapsed)) in self.collapsed.iter().enumerate() {
+            let ratio = reg.progress(*anim) as f32;
             if ratio >= 1. {
-                done.push((n, *id));
+                done.push((n, *anim));
             } else {
                 let k = *orig_row as f32;
+                let fall = reg.eased(*anim, Ease::In) as f32;
                 for (col, val) in collapsed.iter().enumerate() {
-                    let c_size = (CELL_SIZE - 1.) * (1. - ratio);
+                    let c_size = (cell_size() - 1.) * (1. - ratio);
                     let mut color: Color = val.unwrap().into();
                     color.a = (0.5) * (1. - ratio);
                     draw_rectangle(
-                        col as f32 * CELL_SIZE + (0.5 * ratio) * CELL_SIZE + 11.,
-                        (k + 3. * ratio).max(0.) * CELL_SIZE + 11.,
+                        col as f32 * cell_size() + (0.5 * ratio) * cell_size() + 11.,
+                        (k + 3. * fall).max(0.) * cell_size() + 11.,
                         c_size,
                         c_size,
                         color,
@@ -158,13 +243,82 @@ impl Grid {
             }
         }
 
-        for (l, (i, id)) in done.iter().enumerate() {
+        for (l, (i, anim)) in done.iter().enumerate() {
             self.collapsed.remove(i - l);
-            reg.remove_animation(*id);
+            reg.stop(*anim);
+            if let Anim::RowClear(id) = anim {
+                reg.release_id(*id);
+            }
+        }
+        self.particles.draw(reg);
+    }
+
+    /// Takes over the rows still collapsing on `old`, which this grid replaces, so they finish and
+    /// hand their ids back
+    pub fn take_animations(&mut self, old: &mut Grid) {
+        self.collapsed = std::mem::take(&mut old.collapsed);
+        self.particles = std::mem::take(&mut old.particles);
+    }
+
+    /// Kicks up dust under a piece that just landed
+    pub fn land(&mut self, piece: &Tetromino, reg: &AnimationRegulator) {
+        self.particles.dust(piece, reg);
+    }
+
+    pub fn is_clear(&self) -> bool {
+        self.values.iter().flatten().all(Option::is_none)
+    }
+
+    fn is_blocked(&self, pos: &Vec2) -> bool {
+        !self.is_cell_contained(pos) || !self.is_empty(pos)
+    }
+
+    /// Three-corner rule: a T piece that was rotated into place counts as a T-spin when at
+    /// least three of the corners around its centre are walls or blocks
+    pub fn is_tspin(&self, piece: &Tetromino) -> bool {
+        [(0., 0.), (0., 2.), (2., 0.), (2., 2.)]
+            .into_iter()
+            .filter(|&(row, col)| self.is_blocked(&(piece.offset + Vec2::new(row, col))))
+            .count()
+            >= 3
+    }
+
+    /// Empties the hidden rows and the top `rows` visible rows of the board
+    pub fn clear_top(&mut self, rows: usize) {
+        for row in self.values.iter_mut().take(buffer_rows() + rows) {
+            row.fill(None);
         }
     }
 
+    /// Pushes the stack up with `rows` garbage rows that all have a gap at `hole`, returns false
+    /// when that pushed blocks off the top of the hidden rows
+    pub fn add_garbage(&mut self, rows: usize, hole: usize) -> bool {
+        let rows = rows.min(self.values.len());
+        let mut garbage = vec![Some(TType::Garbage); num_cols()];
+        garbage[hole] = None;
+        let fits = self.values[..rows]
+            .iter()
+            .all(|row| row.iter().all(Option::is_none));
+        self.values.drain(..rows);
+        self.values.extend((0..rows).map(|_| garbage.clone()));
+        fits
+    }
+
     pub fn set_type(&mut self, pos: &Vec2, col: TType) {
-        self.values[pos.row as usize][pos.col as usize] = Some(col)
+        self.values[Self::index(pos)][pos.col as usize] = Some(col)
+    }
+
+    pub fn clear_cell(&mut self, pos: &Vec2) {
+        self.values[Self::index(pos)][pos.col as usize] = None
+    }
+
+    /// The visible rows
+    pub fn values(&self) -> &[Row] {
+        &self.values[buffer_rows()..]
+    }
+
+    /// Every row, the hidden ones first
+    pub fn all_values(&self) -> &[Row] {
+        &self.values
     }
 }
@@ -0,0 +1,40 @@
+use crate::{
+    constants::UNDO_LIMIT, data::GameData, finesse::Finesse, grid::Grid, selector::Selector,
+};
+
+/// Everything needed to put the game back the way it was right before a piece locked
+pub struct Snapshot {
+    pub grid: Grid,
+    pub selector: Selector,
+    pub data: GameData,
+    pub finesse: Finesse,
+    pub puzzle_lines: usize,
+    pub opener_placed: usize,
+}
+
+#[derive(Default)]
+pub struct History {
+    snapshots: Vec<Snapshot>,
+}
+
+impl History {
+    pub fn push(&mut self, snapshot: Snapshot) {
+        if self.snapshots.len() == UNDO_LIMIT {
+            self.snapshots.remove(0);
+        }
+        self.snapshots.push(snapshot);
+    }
+
+    pub fn pop(&mut self) -> Option<Snapshot> {
+        self.snapshots.pop()
+    }
+
+    /// Oldest first
+    pub fn snapshots(&self) -> &[Snapshot] {
+        &self.snapshots
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+}
@@ -0,0 +1,111 @@
+//! What the side panel shows next to the board and where, read from `~/.tetrs/hud.txt` or
+//! `resources/hud.txt` when there is none
+
+use crate::storage;
+
+const BUILTIN_LAYOUT: &str = include_str!("../resources/hud.txt");
+const LAYOUT: &str = "hud.txt";
+
+// Height of the labels when a widget doesn't give one
+const DEFAULT_SIZE: f32 = 50.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Widget {
+    Level,
+    Score,
+    Lines,
+    /// Time since the game started
+    Time,
+    /// Pieces per second
+    Pps,
+    /// Attack per minute, the lines the clears would have sent in versus
+    Apm,
+    Combo,
+    Next,
+    /// How many of each piece were placed
+    Stats,
+    /// The goal of a puzzle, the progress of an opener or the faults in finesse
+    Mode,
+}
+
+impl Widget {
+    const ALL: [Widget; 10] = [
+        Widget::Level,
+        Widget::Score,
+        Widget::Lines,
+        Widget::Time,
+        Widget::Pps,
+        Widget::Apm,
+        Widget::Combo,
+        Widget::Next,
+        Widget::Stats,
+        Widget::Mode,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Widget::Level => "level",
+            Widget::Score => "score",
+            Widget::Lines => "lines",
+            Widget::Time => "time",
+            Widget::Pps => "pps",
+            Widget::Apm => "apm",
+            Widget::Combo => "combo",
+            Widget::Next => "next",
+            Widget::Stats => "stats",
+            Widget::Mode => "mode",
+        }
+    }
+}
+
+/// A widget and where it goes, with `x` from the left of the side panel
+pub struct Placement {
+    pub widget: Widget,
+    pub x: f32,
+    pub y: f32,
+    /// How much bigger than the default it is drawn
+    pub scale: f32,
+}
+
+/// Parses a layout, see `resources/hud.txt` for the format
+pub fn parse(source: &str) -> Result<Vec<Placement>, String> {
+    storage::parse_pairs(source)
+        .into_iter()
+        .map(|(key, value)| {
+            let widget = Widget::ALL
+                .into_iter()
+                .find(|widget| widget.name() == key)
+                .ok_or_else(|| {
+                    let names = Widget::ALL.map(|widget| widget.name());
+                    format!("no widget called `{key}`, pick one of {}", names.join(", "))
+                })?;
+            let numbers = value
+                .split(',')
+                .map(|n| n.trim().parse::<f32>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| format!("`{value}` should be numbers, like `0, 20, 50`"))?;
+            let (x, y, size) = match numbers[..] {
+                [x, y] => (x, y, DEFAULT_SIZE),
+                [x, y, size] if size > 0. => (x, y, size),
+                _ => return Err(format!("{key} needs `x, y` or `x, y, size`")),
+            };
+            Ok(Placement {
+                widget,
+                x,
+                y,
+                scale: size / DEFAULT_SIZE,
+            })
+        })
+        .collect()
+}
+
+/// The layout in `~/.tetrs/hud.txt`, or the built-in one without it or when it can't be read
+pub fn load() -> Vec<Placement> {
+    if let Some(source) = storage::read(LAYOUT) {
+        match parse(&source) {
+            Ok(layout) => return layout,
+            Err(err) => eprintln!("Skipping {LAYOUT}: {err}"),
+        }
+    }
+    parse(BUILTIN_LAYOUT).unwrap_or_default()
+}
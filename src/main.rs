@@ -1,16 +1,41 @@
+mod ai;
 mod animator;
+mod args;
+mod bot;
 mod constants;
 mod data;
+mod editor;
+mod effects;
+mod finesse;
+mod fumen;
 mod game;
 mod grid;
+mod history;
+mod hud;
+mod net;
+mod opener;
+mod particles;
+mod player;
+mod puzzle;
+mod screen;
 mod selector;
+mod settings;
+mod sheet;
+mod sim;
 mod sounds;
+mod storage;
+mod stream;
 mod tetromino;
+mod theme;
+mod versus;
 
+use bot::Bot;
 use constants::*;
 use game::Game;
 use macroquad::prelude::*;
 use miniquad::conf::Icon;
+use net::Lobby;
+use stream::StreamServer;
 
 fn load_img(bytes: &'static [u8]) -> Image {
     Image::from_file_with_format(bytes, Some(ImageFormat::Png)).unwrap()
@@ -45,17 +70,70 @@ fn window_conf() -> Conf {
     );
     Conf {
         window_title: "❤️ Tetrs ❤️".to_owned(),
-        window_width: WINDOW_WIDTH,
+        window_width: window_width(),
         window_height: WINDOW_HEIGHT,
-        window_resizable: false,
+        window_resizable: true,
         high_dpi: true,
         icon: Some(Icon { small, medium, big }),
         ..Default::default()
     }
 }
 
-#[macroquad::main(window_conf)]
-async fn main() {
-    let mut game = Game::new().await;
-    game.run().await
+fn main() {
+    if std::env::args().nth(1).as_deref() == Some("sim") {
+        let args = args::SimArgs::parse().unwrap_or_else(|err| {
+            eprintln!("{err}\n\n{}", args::SIM_USAGE);
+            std::process::exit(2);
+        });
+        if let Err(err) = sim::run(args) {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+    let args = args::Args::parse().unwrap_or_else(|err| {
+        eprintln!("{err}\n\n{}", args::USAGE);
+        std::process::exit(2);
+    });
+    macroquad::Window::from_config(window_conf(), async move {
+        // Loading a skin needs the window, so this can't happen with the other arguments
+        if let Err(err) = theme::init(args.theme.as_deref()) {
+            eprintln!("{err}");
+            std::process::exit(2);
+        }
+        let mut game = Game::new().await;
+        if let Some(effect) = args.effect {
+            game.use_effect(&effect).unwrap_or_else(|err| {
+                eprintln!("{err}");
+                std::process::exit(2);
+            });
+        }
+        if let Some(fumen) = args.fumen {
+            game.open_fumen(fumen);
+        }
+        if let Some(command) = args.bot {
+            game.play_with(Bot::spawn(&command).unwrap_or_else(|err| {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }));
+        }
+        if let Some(address) = args.stream {
+            game.stream_to(StreamServer::bind(&address).unwrap_or_else(|err| {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }));
+        }
+        let lobby = match args.online {
+            Some(args::Online::Host(address)) => Some(Lobby::host(&address)),
+            Some(args::Online::Join(address)) => Some(Ok(Lobby::join(&address))),
+            None => None,
+        };
+        if let Some(lobby) = lobby {
+            game.connect(lobby.unwrap_or_else(|err| {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }));
+        }
+        game.run().await
+    });
 }
@@ -0,0 +1,387 @@
+//! Online versus over a plain TCP connection. Each side only simulates its own board and tells
+//! the other what happened to it, one message per line:
+//!
+//! ```text
+//! HELLO <version> <cols> <rows>                first line from both sides, with the board size
+//! START <seed>                                 host to guest, once both said hello
+//! MOVE <rotation> <row> <col>                  the falling piece moved
+//! LOCK <piece> <rotation> <row> <col> <incoming>  it locked there, with garbage still incoming
+//! GARBAGE <rows> <hole>                        garbage came up on the sender's board
+//! ATTACK <lines>                               garbage sent to the receiver
+//! LOST                                         the sender topped out
+//! ```
+//!
+//! Both boards deal pieces from the same seed, so a lock only has to say where the piece went
+//! and the piece type is there to catch the two sides drifting apart.
+
+use std::{
+    io::{ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    constants::{num_cols, num_rows},
+    tetromino::TType,
+};
+
+// Bumped whenever a message changes, both sides have to be on the same version to play
+pub const PROTOCOL_VERSION: u32 = 3;
+pub const DEFAULT_PORT: u16 = 7878;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Message {
+    Hello {
+        version: u32,
+        cols: usize,
+        rows: usize,
+    },
+    Start(u64),
+    Move {
+        rotation: usize,
+        row: i32,
+        col: i32,
+    },
+    Lock {
+        piece: TType,
+        rotation: usize,
+        row: i32,
+        col: i32,
+        incoming: u32,
+    },
+    Garbage {
+        rows: usize,
+        hole: usize,
+    },
+    Attack(u32),
+    Lost,
+}
+
+impl Message {
+    /// The hello of this side, on the board it is playing on
+    pub fn hello() -> Self {
+        Message::Hello {
+            version: PROTOCOL_VERSION,
+            cols: num_cols(),
+            rows: num_rows(),
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        match self {
+            Message::Hello {
+                version,
+                cols,
+                rows,
+            } => format!("HELLO {version} {cols} {rows}"),
+            Message::Start(seed) => format!("START {seed}"),
+            Message::Move { rotation, row, col } => format!("MOVE {rotation} {row} {col}"),
+            Message::Lock {
+                piece,
+                rotation,
+                row,
+                col,
+                incoming,
+            } => format!("LOCK {} {rotation} {row} {col} {incoming}", piece.to_char()),
+            Message::Garbage { rows, hole } => format!("GARBAGE {rows} {hole}"),
+            Message::Attack(lines) => format!("ATTACK {lines}"),
+            Message::Lost => "LOST".to_owned(),
+        }
+    }
+
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("");
+        let args = words.collect::<Vec<_>>();
+        let invalid = || format!("invalid message `{line}`");
+        let number = |n: usize| -> Result<i64, String> {
+            args.get(n)
+                .and_then(|arg| arg.parse().ok())
+                .ok_or_else(invalid)
+        };
+
+        Ok(match (command, args.len()) {
+            // Versions before the board size only sent their version, enough to turn them away
+            ("HELLO", 1) => Message::Hello {
+                version: number(0)? as u32,
+                cols: 0,
+                rows: 0,
+            },
+            ("HELLO", 3) => Message::Hello {
+                version: number(0)? as u32,
+                cols: number(1)? as usize,
+                rows: number(2)? as usize,
+            },
+            ("START", 1) => Message::Start(args[0].parse().map_err(|_| invalid())?),
+            ("MOVE", 3) => Message::Move {
+                rotation: number(0)? as usize % 4,
+                row: number(1)? as i32,
+                col: number(2)? as i32,
+            },
+            ("LOCK", 5) => Message::Lock {
+                piece: args[0]
+                    .chars()
+                    .next()
+                    .and_then(TType::from_char)
+                    .ok_or_else(invalid)?,
+                rotation: number(1)? as usize % 4,
+                row: number(2)? as i32,
+                col: number(3)? as i32,
+                incoming: number(4)? as u32,
+            },
+            ("GARBAGE", 2) => Message::Garbage {
+                rows: number(0)? as usize,
+                hole: number(1)? as usize,
+            },
+            ("ATTACK", 1) => Message::Attack(number(0)? as u32),
+            ("LOST", 0) => Message::Lost,
+            _ => return Err(invalid()),
+        })
+    }
+}
+
+/// A non-blocking line based connection, polled once a frame
+pub struct Connection {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+    // Why the other side stopped sending, reported once everything it sent was received
+    closed: Option<String>,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> Result<Self, String> {
+        stream
+            .set_nonblocking(true)
+            .and_then(|_| stream.set_nodelay(true))
+            .map_err(|err| err.to_string())?;
+        Ok(Self {
+            stream,
+            incoming: Vec::new(),
+            outgoing: Vec::new(),
+            closed: None,
+        })
+    }
+
+    pub fn send(&mut self, message: Message) -> Result<(), String> {
+        self.outgoing.extend(message.encode().bytes());
+        self.outgoing.push(b'\n');
+        self.flush()
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err("connection closed".to_owned()),
+                Ok(n) => drop(self.outgoing.drain(..n)),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err.to_string()),
+            }
+        }
+        Ok(())
+    }
+
+    /// The next message that fully arrived, if any. A closed connection is only reported once
+    /// every message that came before it was handed out, so a last LOST isn't missed.
+    pub fn receive(&mut self) -> Result<Option<Message>, String> {
+        let mut buffer = [0; 1024];
+        while self.closed.is_none() {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => self.closed = Some("connection closed".to_owned()),
+                Ok(n) => self.incoming.extend(&buffer[..n]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) => self.closed = Some(err.to_string()),
+            }
+        }
+        if let Some(end) = self.incoming.iter().position(|&b| b == b'\n') {
+            let line = self.incoming.drain(..=end).collect::<Vec<_>>();
+            return Message::parse(String::from_utf8_lossy(&line).trim()).map(Some);
+        }
+        if let Some(reason) = &self.closed {
+            return Err(reason.clone());
+        }
+        self.flush()?;
+        Ok(None)
+    }
+}
+
+/// Waits for the other player and says hello, then hands over the connection and the seed the
+/// match is played with
+pub struct Lobby {
+    listener: Option<TcpListener>,
+    // Connecting blocks, so the guest connects on another thread and hears back here
+    connecting: Option<Receiver<Result<TcpStream, String>>>,
+    connection: Option<Connection>,
+    is_host: bool,
+    pub status: String,
+}
+
+impl Lobby {
+    pub fn host(address: &str) -> Result<Self, String> {
+        let listener = TcpListener::bind(address)
+            .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+            .map_err(|err| format!("could not listen on {address}: {err}"))?;
+        Ok(Self {
+            listener: Some(listener),
+            connecting: None,
+            connection: None,
+            is_host: true,
+            status: format!("WAITING ON {address}"),
+        })
+    }
+
+    pub fn join(address: &str) -> Self {
+        let (sender, connecting) = mpsc::channel();
+        let target = address.to_owned();
+        thread::spawn(move || {
+            let failed = |err: String| format!("could not connect to {target}: {err}");
+            let stream = target
+                .to_socket_addrs()
+                .map_err(|err| failed(err.to_string()))
+                .and_then(|mut resolved| {
+                    resolved
+                        .next()
+                        .ok_or_else(|| failed("no such address".to_owned()))
+                })
+                .and_then(|resolved| {
+                    TcpStream::connect_timeout(&resolved, Duration::from_secs(5))
+                        .map_err(|err| failed(err.to_string()))
+                });
+            let _ = sender.send(stream);
+        });
+        Self {
+            listener: None,
+            connecting: Some(connecting),
+            connection: None,
+            is_host: false,
+            status: format!("CONNECTING TO {address}"),
+        }
+    }
+
+    pub fn poll(&mut self) -> Result<Option<(Connection, u64)>, String> {
+        if let Some(listener) = &self.listener {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let mut connection = Connection::new(stream)?;
+                    connection.send(Message::hello())?;
+                    self.connection = Some(connection);
+                    self.listener = None;
+                    self.status = "SAYING HELLO".to_owned();
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(None),
+                Err(err) => return Err(err.to_string()),
+            }
+        }
+        if let Some(connecting) = &self.connecting {
+            match connecting.try_recv() {
+                Ok(stream) => {
+                    let mut connection = Connection::new(stream?)?;
+                    connection.send(Message::hello())?;
+                    self.connection = Some(connection);
+                    self.connecting = None;
+                    self.status = "SAYING HELLO".to_owned();
+                }
+                Err(TryRecvError::Empty) => return Ok(None),
+                Err(TryRecvError::Disconnected) => return Err("could not connect".to_owned()),
+            }
+        }
+        let Some(connection) = &mut self.connection else {
+            return Ok(None);
+        };
+
+        while let Some(message) = connection.receive()? {
+            match message {
+                Message::Hello { version, .. } if version != PROTOCOL_VERSION => {
+                    return Err(format!(
+                    "version mismatch, this is v{PROTOCOL_VERSION} and the other side v{version}"
+                ))
+                }
+                Message::Hello { cols, rows, .. } if (cols, rows) != (num_cols(), num_rows()) => {
+                    return Err(format!(
+                        "board size mismatch, this is {}x{} and the other side {cols}x{rows}",
+                        num_cols(),
+                        num_rows()
+                    ))
+                }
+                Message::Hello { .. } if self.is_host => {
+                    let seed = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0, |time| time.as_nanos() as u64);
+                    connection.send(Message::Start(seed))?;
+                    return Ok(self.connection.take().map(|c| (c, seed)));
+                }
+                Message::Hello { .. } => (),
+                Message::Start(seed) if !self.is_host => {
+                    return Ok(self.connection.take().map(|c| (c, seed)))
+                }
+                message => return Err(format!("unexpected {}", message.encode())),
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hands_out_messages_sent_right_before_closing() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut sender = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let mut connection = Connection::new(listener.accept().unwrap().0).unwrap();
+        sender.write_all(b"ATTACK 2\nLOST\n").unwrap();
+        drop(sender);
+        std::thread::sleep(Duration::from_millis(50));
+
+        assert_eq!(connection.receive(), Ok(Some(Message::Attack(2))));
+        assert_eq!(connection.receive(), Ok(Some(Message::Lost)));
+        assert!(connection.receive().is_err());
+    }
+
+    #[test]
+    fn joins_without_holding_up_a_frame() {
+        let mut host = Lobby::host("127.0.0.1:0").unwrap();
+        let address = host.listener.as_ref().unwrap().local_addr().unwrap();
+        let mut guest = Lobby::join(&address.to_string());
+
+        let (mut hosted, mut joined) = (None, None);
+        for _ in 0..100 {
+            hosted = hosted.or(host.poll().unwrap().map(|(_, seed)| seed));
+            joined = joined.or(guest.poll().unwrap().map(|(_, seed)| seed));
+            if hosted.is_some() && joined.is_some() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(hosted.is_some());
+        assert_eq!(hosted, joined);
+    }
+
+    #[test]
+    fn turns_away_a_different_board_size() {
+        let mut lobby = Lobby::host("127.0.0.1:0").unwrap();
+        let address = lobby.listener.as_ref().unwrap().local_addr().unwrap();
+        let mut guest = TcpStream::connect(address).unwrap();
+        guest.write_all(b"HELLO 3 4 20\n").unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+
+        let err = lobby.poll().map(|_| ()).unwrap_err();
+        assert_eq!(
+            err,
+            format!(
+                "board size mismatch, this is {}x{} and the other side 4x20",
+                num_cols(),
+                num_rows()
+            )
+        );
+    }
+
+    #[test]
+    fn hello_round_trips() {
+        let hello = Message::hello();
+        assert_eq!(Message::parse(&hello.encode()), Ok(hello));
+    }
+}
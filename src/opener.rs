@@ -0,0 +1,89 @@
+use macroquad::prelude::*;
+
+use crate::{
+    constants::cell_size,
+    data::Vec2,
+    grid::{Grid, GridValues},
+    sheet::{self, Sheet},
+    tetromino::{TType, Tetromino},
+};
+
+const BUILTIN_OPENERS: &str = include_str!("../resources/openers.txt");
+
+#[derive(Debug, Clone)]
+pub struct Opener {
+    pub name: String,
+    pub pieces: Vec<TType>,
+    pub target: GridValues,
+}
+
+impl Opener {
+    /// Parses a single opener, see [`Sheet`] for the layout. The target marks where each piece
+    /// has to end up with its own letter, and the pieces are dealt in the order given:
+    ///
+    /// ```text
+    /// name = Tetris Stack
+    /// pieces = I J T S Z L O
+    /// target =
+    /// .S........
+    /// TSSLLLOOJ.
+    /// TTSLZZOOJ.
+    /// TIIIIZZJJ.
+    /// ```
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let sheet = Sheet::parse(source, Some("target"), &["name", "pieces"])?;
+        let opener = Self {
+            name: sheet.get("name")?.to_owned(),
+            pieces: sheet.pieces("pieces")?,
+            target: sheet.board()?,
+        };
+
+        for ty in TType::PIECES {
+            let cells = opener.target.iter().flatten().filter(|&&c| c == Some(ty));
+            let pieces = opener.pieces.iter().filter(|&&p| p == ty);
+            if cells.count() != pieces.count() * 4 {
+                return Err(format!("target cells for {ty:?} don't match the pieces"));
+            }
+        }
+        Ok(opener)
+    }
+
+    /// The built-in openers followed by every `.txt` file in `~/.tetrs/openers`
+    pub fn load_all() -> Vec<Self> {
+        sheet::load_all(BUILTIN_OPENERS, "openers", Self::parse)
+    }
+
+    /// Whether every cell of `piece` is on a target cell meant for its type
+    pub fn fits(&self, piece: &Tetromino) -> bool {
+        piece.get_curr_positions().iter().all(|tile| {
+            tile.row >= 0.
+                && tile.col >= 0.
+                && self
+                    .target
+                    .get(tile.row as usize)
+                    .and_then(|row| row.get(tile.col as usize))
+                    == Some(&Some(piece._type))
+        })
+    }
+
+    /// Shows the target cells that haven't been filled yet
+    pub fn draw_overlay(&self, grid: &Grid) {
+        for (row, valr) in self.target.iter().enumerate() {
+            for (col, val) in valr.iter().enumerate() {
+                let Some(ty) = val else { continue };
+                if !grid.is_empty(&Vec2::from((row, col))) {
+                    continue;
+                }
+                let (x, y) = (
+                    col as f32 * cell_size() + 11.,
+                    row as f32 * cell_size() + 11.,
+                );
+                let mut colour: Color = (*ty).into();
+                colour.a = 0.25;
+                draw_rectangle(x, y, cell_size() - 1., cell_size() - 1., colour);
+                colour.a = 0.6;
+                draw_rectangle_lines(x, y, cell_size() - 1., cell_size() - 1., 2., colour);
+            }
+        }
+    }
+}
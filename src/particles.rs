@@ -0,0 +1,133 @@
+use macroquad::prelude::*;
+
+use crate::{
+    animator::AnimationRegulator,
+    constants::cell_size,
+    settings::PARTICLES,
+    tetromino::{TType, Tetromino},
+    theme::theme,
+};
+
+const SPARKS_PER_CELL: usize = 3;
+const SPARK_LIFE: f64 = 0.7;
+const SPARK_GRAVITY: f32 = 700.;
+const DUST_PER_CELL: usize = 4;
+const DUST_LIFE: f64 = 0.4;
+
+#[derive(Clone)]
+struct Particle {
+    origin: Vec2,
+    velocity: Vec2,
+    gravity: f32,
+    size: f32,
+    colour: Color,
+    born: f64,
+    life: f64,
+}
+
+impl Particle {
+    /// Where it is `age` seconds after it was born
+    fn position(&self, age: f32) -> Vec2 {
+        self.origin + self.velocity * age + vec2(0., self.gravity) * age * age / 2.
+    }
+}
+
+/// Sparks flying off cleared rows and dust kicked up where pieces land, in board coordinates
+#[derive(Clone, Default)]
+pub struct Particles {
+    particles: Vec<Particle>,
+    // Its own random numbers, so drawing never changes the pieces or garbage a seed deals
+    seed: u32,
+}
+
+impl Particles {
+    fn random(&mut self, min: f32, max: f32) -> f32 {
+        self.seed = self.seed.wrapping_mul(1664525).wrapping_add(1013904223);
+        min + (self.seed >> 8) as f32 / (1 << 24) as f32 * (max - min)
+    }
+
+    // Without a window nothing is drawn, and time standing still would keep them forever
+    fn enabled(reg: &AnimationRegulator) -> bool {
+        PARTICLES.is_on() && !reg.is_headless()
+    }
+
+    /// Sparks in the colour of every cell of the visible `row` that was cleared
+    pub fn sparks(&mut self, row: usize, cells: &[Option<TType>], reg: &AnimationRegulator) {
+        if !Self::enabled(reg) {
+            return;
+        }
+        for (col, cell) in cells.iter().enumerate() {
+            let Some(ty) = cell else {
+                continue;
+            };
+            let centre = vec2(
+                (col as f32 + 0.5) * cell_size() + 11.,
+                (row as f32 + 0.5) * cell_size() + 11.,
+            );
+            for _ in 0..SPARKS_PER_CELL {
+                let velocity = vec2(self.random(-180., 180.), self.random(-320., -80.));
+                let size = self.random(2., 4.);
+                self.particles.push(Particle {
+                    origin: centre,
+                    velocity,
+                    gravity: SPARK_GRAVITY,
+                    size,
+                    colour: (*ty).into(),
+                    born: reg.now(),
+                    life: SPARK_LIFE,
+                });
+            }
+        }
+    }
+
+    /// Dust along the bottom of a piece that just landed
+    pub fn dust(&mut self, piece: &Tetromino, reg: &AnimationRegulator) {
+        if !Self::enabled(reg) {
+            return;
+        }
+        let tiles = piece.get_curr_positions();
+        for tile in &tiles {
+            let below = tiles
+                .iter()
+                .any(|other| other.col == tile.col && other.row == tile.row + 1.);
+            if below || tile.row < 0. {
+                continue;
+            }
+            let bottom = (tile.row + 1.) * cell_size() + 10.;
+            for _ in 0..DUST_PER_CELL {
+                let x = tile.col * cell_size() + 11. + self.random(0., cell_size());
+                let velocity = vec2(self.random(-60., 60.), self.random(-50., -15.));
+                let size = self.random(1.5, 3.5);
+                self.particles.push(Particle {
+                    origin: vec2(x, bottom),
+                    velocity,
+                    gravity: 0.,
+                    size,
+                    colour: theme().dim,
+                    born: reg.now(),
+                    life: DUST_LIFE,
+                });
+            }
+        }
+    }
+
+    /// Draws the living particles and forgets the rest
+    pub fn draw(&mut self, reg: &AnimationRegulator) {
+        let now = reg.now();
+        self.particles
+            .retain(|particle| now - particle.born < particle.life);
+        for particle in &self.particles {
+            let age = (now - particle.born) as f32;
+            let position = particle.position(age);
+            let mut colour = particle.colour;
+            colour.a *= 1. - age / particle.life as f32;
+            draw_rectangle(
+                position.x - particle.size / 2.,
+                position.y - particle.size / 2.,
+                particle.size,
+                particle.size,
+                colour,
+            );
+        }
+    }
+}
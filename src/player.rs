@@ -0,0 +1,294 @@
+use macroquad::prelude::*;
+
+use crate::{
+    animator::AnimationRegulator,
+    constants::{cell_size, GLIDE_SNAP, GLIDE_SPEED},
+    data::{GameData, Vec2},
+    grid::Grid,
+    selector::Selector,
+    settings::SMOOTH,
+    sounds::Sounds,
+    tetromino::{TType, Tetromino},
+};
+
+/// What a player asked the falling piece to do this frame
+#[derive(Default, Clone, Copy)]
+pub struct Input {
+    pub left: bool,
+    pub right: bool,
+    pub rotate: bool,
+    pub soft_drop: bool,
+}
+
+/// The keys one player moves their piece with
+#[derive(Clone, Copy)]
+pub struct Controls {
+    pub left: KeyCode,
+    pub right: KeyCode,
+    pub rotate: KeyCode,
+    pub soft_drop: KeyCode,
+}
+
+impl Controls {
+    pub const ARROWS: Controls = Controls {
+        left: KeyCode::Left,
+        right: KeyCode::Right,
+        rotate: KeyCode::Up,
+        soft_drop: KeyCode::Down,
+    };
+
+    pub const WASD: Controls = Controls {
+        left: KeyCode::A,
+        right: KeyCode::D,
+        rotate: KeyCode::W,
+        soft_drop: KeyCode::S,
+    };
+
+    pub fn poll(&self) -> Input {
+        Input {
+            left: is_key_pressed(self.left),
+            right: is_key_pressed(self.right),
+            rotate: is_key_pressed(self.rotate),
+            soft_drop: is_key_down(self.soft_drop),
+        }
+    }
+}
+
+/// Where the falling piece is drawn, catching up with where it is a little after every move
+#[derive(Clone, Copy, Default)]
+struct Glide {
+    // None until the first frame of a new piece, which is drawn where it is
+    tiles: Option<[Vec2; 4]>,
+    // Which piece of the game it is
+    piece: u32,
+    drawn_at: f64,
+}
+
+impl Glide {
+    /// Moves the drawn tiles towards `target` for the time since the last frame
+    fn follow(&mut self, target: [Vec2; 4], piece: u32, now: f64) -> [Vec2; 4] {
+        let far = |tiles: &[Vec2; 4]| {
+            tiles.iter().zip(&target).any(|(tile, to)| {
+                (tile.row - to.row).abs() > GLIDE_SNAP || (tile.col - to.col).abs() > GLIDE_SNAP
+            })
+        };
+        let tiles = match self.tiles {
+            Some(tiles) if self.piece == piece && !far(&tiles) => {
+                // The same share of the way is left after every equal stretch of time, whatever
+                // the frame rate
+                let step = 1. - (-GLIDE_SPEED * (now - self.drawn_at) as f32).exp();
+                let mut tiles = tiles;
+                for (tile, to) in tiles.iter_mut().zip(target) {
+                    tile.row += (to.row - tile.row) * step;
+                    tile.col += (to.col - tile.col) * step;
+                }
+                tiles
+            }
+            _ => target,
+        };
+        *self = Self {
+            tiles: Some(tiles),
+            piece,
+            drawn_at: now,
+        };
+        tiles
+    }
+}
+
+/// One board and the pieces falling into it
+#[derive(Clone, Default)]
+pub struct Player {
+    pub grid: Grid,
+    pub selector: Selector,
+    pub data: GameData,
+    // Set when a piece locks so holding soft drop doesn't carry over into the next one
+    pub has_current_changed: bool,
+    // Whether the last thing the current piece did was rotate, for T-spins
+    pub last_rotated: bool,
+    // Garbage rows waiting to be added once this player locks a piece without clearing
+    pub incoming: u32,
+    glide: Glide,
+}
+
+impl Player {
+    pub fn new(grid: Grid, selector: Selector, data: GameData) -> Self {
+        let mut player = Self {
+            grid,
+            selector,
+            data,
+            ..Default::default()
+        };
+        player.enter();
+        player
+    }
+
+    pub fn is_inside(&self, block: &Tetromino) -> bool {
+        block
+            .get_curr_positions()
+            .iter()
+            .all(|tile| self.grid.is_cell_contained(tile) && self.grid.is_empty(tile))
+    }
+
+    pub fn is_block_inside(&self) -> bool {
+        self.is_inside(&self.selector.current)
+    }
+
+    pub fn is_ghost_inside(&self) -> bool {
+        self.is_inside(&self.selector.ghost)
+    }
+
+    pub fn is_tspin(&self) -> bool {
+        let piece = &self.selector.current;
+        piece._type == TType::T && self.last_rotated && self.grid.is_tspin(piece)
+    }
+
+    /// Moves the falling piece, returns true once it has landed and has to be locked
+    pub fn update(&mut self, input: Input, sounds: &Sounds) -> bool {
+        if input.right {
+            self.selector.current.move_pos(Vec2::new(0., 1.));
+            if !self.is_block_inside() {
+                self.selector.current.move_pos(Vec2::new(0., -1.));
+            } else {
+                self.last_rotated = false;
+                sounds.play(3)
+            }
+        }
+        if input.left {
+            self.selector.current.move_pos(Vec2::new(0., -1.));
+            if !self.is_block_inside() {
+                self.selector.current.move_pos(Vec2::new(0., 1.));
+            } else {
+                self.last_rotated = false;
+                sounds.play(3)
+            }
+        }
+        if input.soft_drop {
+            if !self.has_current_changed {
+                self.selector.current.move_pos(Vec2::new(1., 0.));
+                if !self.is_block_inside() {
+                    self.selector.current.move_pos(Vec2::new(-1., 0.));
+                    return true;
+                } else {
+                    self.last_rotated = false;
+                    self.data.add_to_score(1);
+                }
+            }
+        } else {
+            self.has_current_changed = false;
+        }
+        if input.rotate {
+            self.selector.current.rotate();
+            if !self.is_block_inside() {
+                self.selector.current.undo_rotate();
+            } else {
+                self.last_rotated = true;
+                sounds.play(2)
+            }
+        }
+
+        if self.data.has_dropped() {
+            self.selector.current.move_pos(Vec2::new(1., 0.));
+            if !self.is_block_inside() {
+                self.selector.current.move_pos(Vec2::new(-1., 0.));
+                return true;
+            } else {
+                self.last_rotated = false;
+            }
+        }
+        false
+    }
+
+    pub fn update_ghost(&mut self) {
+        self.selector.ghost.offset = self.selector.current.offset;
+        self.selector.ghost.rotation = self.selector.current.rotation;
+        while self.is_ghost_inside() {
+            self.selector.ghost.move_pos(Vec2::new(1., 0.))
+        }
+        self.selector.ghost.move_pos(Vec2::new(-1., 0.));
+    }
+
+    /// Lock out: whether the current piece is entirely above the visible field, checked as it locks
+    pub fn is_locked_out(&self) -> bool {
+        self.selector
+            .current
+            .get_curr_positions()
+            .iter()
+            .all(|tile| tile.row < 0.)
+    }
+
+    /// Brings in the next piece once the current one locked, returns false on a block out
+    pub fn spawn_next(&mut self) -> bool {
+        self.selector.block_locked();
+        self.last_rotated = false;
+        self.has_current_changed = true;
+        self.enter()
+    }
+
+    /// Puts the current piece back where it spawned, returns false on a block out
+    pub fn respawn(&mut self) -> bool {
+        self.selector.respawn();
+        self.last_rotated = false;
+        self.has_current_changed = true;
+        self.enter()
+    }
+
+    /// A piece that spawns on top of the stack is a block out. Otherwise it drops a row straight
+    /// away when nothing is in the way, so it shows up right at the top of the visible field.
+    fn enter(&mut self) -> bool {
+        if !self.is_block_inside() {
+            return false;
+        }
+        self.selector.current.move_pos(Vec2::new(1., 0.));
+        if !self.is_block_inside() {
+            self.selector.current.move_pos(Vec2::new(-1., 0.));
+        }
+        true
+    }
+
+    /// Writes the current piece into the grid where it is
+    pub fn place(&mut self) {
+        let piece = self.selector.current;
+        for tile in piece.get_curr_positions() {
+            self.grid.set_type(&tile, piece._type);
+        }
+        self.data.pieces += 1;
+        if let Some(placed) = self.data.placed.get_mut(piece._type as usize) {
+            *placed += 1;
+        }
+    }
+
+    pub async fn draw_pieces(&mut self, reg: &AnimationRegulator) {
+        let current = &self.selector.current;
+        match SMOOTH.is_on() {
+            true => {
+                let tiles = current.get_curr_positions();
+                let tiles = self.glide.follow(tiles, self.data.pieces, reg.now());
+                current.draw_tiles(&tiles);
+            }
+            false => current.draw().await,
+        }
+        self.selector.ghost.draw_outline(reg).await;
+    }
+
+    /// Draws the next piece centred in a 130 by 140 box with its top left at `x`, `y`, all of it
+    /// scaled by `scale`
+    pub async fn draw_next(&self, x: f32, y: f32, scale: f32) {
+        let mut next = self.selector.next;
+        next.offset = Vec2::new(0., 0.);
+        let cells = next.get_curr_positions();
+        let min = |f: fn(&Vec2) -> f32| cells.iter().map(f).fold(f32::MAX, f32::min);
+        let max = |f: fn(&Vec2) -> f32| cells.iter().map(f).fold(f32::MIN, f32::max);
+        let (top, left) = (min(|c| c.row), min(|c| c.col));
+        let size = cell_size() * scale;
+        let height = (max(|c| c.row) - top + 1.) * size;
+        let width = (max(|c| c.col) - left + 1.) * size;
+        next.draw_with_offset(
+            Vec2::new(
+                y + (140. * scale - height) / 2. - top * size,
+                x + (130. * scale - width) / 2. - left * size,
+            ),
+            size,
+        )
+        .await
+    }
+}
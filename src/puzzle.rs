@@ -0,0 +1,126 @@
+use crate::{
+    grid::GridValues,
+    sheet::{self, Sheet},
+    tetromino::TType,
+};
+
+const BUILTIN_PUZZLES: &str = include_str!("../resources/puzzles.txt");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Goal {
+    Lines(usize),
+    PerfectClear,
+    TSpin(usize),
+}
+
+impl Goal {
+    fn parse(value: &str) -> Result<Self, String> {
+        let mut words = value.split_whitespace();
+        let count = |word: Option<&str>| match word {
+            Some("single") => Ok(1),
+            Some("double") => Ok(2),
+            Some("triple") => Ok(3),
+            Some(n) => n.parse().map_err(|_| format!("invalid count `{n}`")),
+            None => Err("missing count".to_owned()),
+        };
+        match words.next() {
+            Some("lines") => Ok(Goal::Lines(count(words.next())?)),
+            Some("perfect") => Ok(Goal::PerfectClear),
+            Some("tspin") => Ok(Goal::TSpin(count(words.next())?)),
+            _ => Err(format!("unknown goal `{value}`")),
+        }
+    }
+
+    /// Every goal the editor cycles through
+    pub const PRESETS: [Goal; 8] = [
+        Goal::Lines(1),
+        Goal::Lines(2),
+        Goal::Lines(3),
+        Goal::Lines(4),
+        Goal::PerfectClear,
+        Goal::TSpin(1),
+        Goal::TSpin(2),
+        Goal::TSpin(3),
+    ];
+
+    /// The form [`Goal::parse`] reads back
+    fn to_sheet(self) -> String {
+        match self {
+            Goal::Lines(n) => format!("lines {n}"),
+            Goal::PerfectClear => "perfect clear".to_owned(),
+            Goal::TSpin(1) => "tspin single".to_owned(),
+            Goal::TSpin(2) => "tspin double".to_owned(),
+            Goal::TSpin(3) => "tspin triple".to_owned(),
+            Goal::TSpin(n) => format!("tspin {n}"),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Goal::Lines(1) => "CLEAR 1 LINE".to_owned(),
+            Goal::Lines(n) => format!("CLEAR {n} LINES"),
+            Goal::PerfectClear => "PERFECT CLEAR".to_owned(),
+            Goal::TSpin(1) => "T-SPIN SINGLE".to_owned(),
+            Goal::TSpin(2) => "T-SPIN DOUBLE".to_owned(),
+            Goal::TSpin(3) => "T-SPIN TRIPLE".to_owned(),
+            Goal::TSpin(n) => format!("T-SPIN {n} LINES"),
+        }
+    }
+
+    /// Checked after every lock with the lines cleared by that piece and in total
+    pub fn is_met(&self, total: usize, cleared: usize, tspin: bool, perfect_clear: bool) -> bool {
+        match self {
+            Goal::Lines(n) => total >= *n,
+            Goal::PerfectClear => cleared > 0 && perfect_clear,
+            Goal::TSpin(n) => tspin && cleared == *n,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Puzzle {
+    pub name: String,
+    pub goal: Goal,
+    pub pieces: Vec<TType>,
+    pub board: GridValues,
+}
+
+impl Puzzle {
+    /// Parses a single puzzle, see [`Sheet`] for the layout:
+    ///
+    /// ```text
+    /// name = Tetris Ready
+    /// goal = lines 4         # or `perfect clear`, `tspin double`, ...
+    /// pieces = I O T
+    /// board =
+    /// XXXXXXXXX.
+    /// ```
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let sheet = Sheet::parse(source, Some("board"), &["name", "goal", "pieces"])?;
+        Ok(Self {
+            name: sheet.get("name")?.to_owned(),
+            goal: Goal::parse(sheet.get("goal")?)?,
+            pieces: sheet.pieces("pieces")?,
+            board: sheet.board()?,
+        })
+    }
+
+    pub fn to_sheet(&self) -> String {
+        format!(
+            "name = {}\ngoal = {}\npieces = {}\nboard =\n{}",
+            self.name,
+            self.goal.to_sheet(),
+            self.pieces
+                .iter()
+                .map(|p| p.to_char().to_string())
+                .collect::<Vec<_>>()
+                .join(" "),
+            sheet::write_board(&self.board)
+        )
+    }
+
+    /// The built-in puzzles followed by every `.txt` file in `~/.tetrs/puzzles`
+    pub fn load_all() -> Vec<Self> {
+        sheet::load_all(BUILTIN_PUZZLES, "puzzles", Self::parse)
+    }
+}
@@ -0,0 +1,25 @@
+//! The game is always drawn at its own size into a render target, which is then scaled up or
+//! down to fill as much of the window as it can without stretching. The rest of the window is
+//! letterboxed.
+
+use macroquad::prelude::*;
+
+use crate::constants::{window_width, WINDOW_HEIGHT};
+
+/// Where the game goes in the window, in window coordinates
+pub fn viewport() -> Rect {
+    let (width, height) = (window_width() as f32, WINDOW_HEIGHT as f32);
+    let scale = (screen_width() / width).min(screen_height() / height);
+    let (w, h) = (width * scale, height * scale);
+    Rect::new((screen_width() - w) / 2., (screen_height() - h) / 2., w, h)
+}
+
+/// The mouse position in game coordinates, however big the window is
+pub fn mouse_position() -> (f32, f32) {
+    let (x, y) = macroquad::input::mouse_position();
+    let view = viewport();
+    (
+        (x - view.x) / view.w * window_width() as f32,
+        (y - view.y) / view.h * WINDOW_HEIGHT as f32,
+    )
+}
@@ -1,8 +1,15 @@
+use std::collections::VecDeque;
+
 use crate::tetromino::{TType, Tetromino};
 use macroquad::rand::gen_range;
 
+#[derive(Clone)]
 pub struct Selector {
     choices: Vec<TType>,
+    // Pieces that are dealt in order before falling back to the random bag
+    queue: VecDeque<TType>,
+    // State of our own generator when seeded, so that a seed always deals the same pieces
+    rng: Option<u64>,
     pub current: Tetromino,
     pub ghost: Tetromino,
     pub next: Tetromino,
@@ -20,16 +27,68 @@ impl Selector {
         self.choices.push(J);
     }
 
+    /// Random bags drawn from `seed`, the same seed always deals the same pieces
+    pub fn seeded(seed: u64) -> Self {
+        let mut selector = Self {
+            rng: Some(seed),
+            ..Self::default()
+        };
+        selector.choices.clear();
+        selector.current = Tetromino::new(selector.next());
+        selector.ghost = selector.current;
+        selector.next = Tetromino::new(selector.next());
+        selector
+    }
+
+    fn random(&mut self, len: usize) -> usize {
+        match &mut self.rng {
+            Some(state) => {
+                *state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                ((*state >> 33) % len as u64) as usize
+            }
+            None => gen_range(0, len),
+        }
+    }
+
+    /// Deals `sequence` in order before going back to random bags
+    pub fn with_sequence(sequence: &[TType]) -> Self {
+        let mut selector = Self::default();
+        selector.choices.clear();
+        selector.queue = sequence.iter().copied().collect();
+        selector.current = Tetromino::new(selector.next());
+        selector.ghost = selector.current;
+        selector.next = Tetromino::new(selector.next());
+        selector
+    }
+
     pub fn next(&mut self) -> TType {
+        if let Some(queued) = self.queue.pop_front() {
+            return queued;
+        }
         if self.choices.is_empty() {
             self.reset();
         }
-        let chosen = gen_range(0, self.choices.len());
+        let chosen = self.random(self.choices.len());
         let cloned = self.choices[chosen];
         self.choices.remove(chosen);
         cloned
     }
 
+    /// Puts the current piece back at the top, as if it had just spawned
+    pub fn respawn(&mut self) {
+        self.current = Tetromino::new(self.current._type);
+        self.ghost = self.current;
+    }
+
+    /// The pieces known to be coming after the current one, in order
+    pub fn preview(&self) -> Vec<TType> {
+        std::iter::once(self.next._type)
+            .chain(self.queue.iter().copied())
+            .collect()
+    }
+
     pub fn block_locked(&mut self) {
         self.ghost = self.next;
         self.current = self.next;
@@ -57,6 +116,8 @@ impl Default for Selector {
             current,
             next: Tetromino::new(random(&mut choices)),
             choices,
+            queue: VecDeque::new(),
+            rng: None,
         }
     }
 }
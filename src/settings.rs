@@ -0,0 +1,65 @@
+//! Options that are switched on and off while playing and remembered in `~/.tetrs/settings.txt`
+//! as `name = on` or `name = off` lines
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::storage;
+
+const SETTINGS: &str = "settings.txt";
+
+pub struct Setting {
+    pub name: &'static str,
+    on: AtomicBool,
+}
+
+impl Setting {
+    const fn new(name: &'static str, on: bool) -> Self {
+        Self {
+            name,
+            on: AtomicBool::new(on),
+        }
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.on.load(Ordering::Relaxed)
+    }
+
+    /// Flips the setting and remembers it for next time
+    pub fn toggle(&self) {
+        self.on.fetch_xor(true, Ordering::Relaxed);
+        save();
+    }
+}
+
+/// A glyph on every block telling the pieces apart without their colours
+pub static PATTERNS: Setting = Setting::new("patterns", false);
+/// Sparks on line clears and dust where pieces land
+pub static PARTICLES: Setting = Setting::new("particles", true);
+/// The falling piece gliding from cell to cell instead of jumping
+pub static SMOOTH: Setting = Setting::new("smooth", true);
+/// The screen shaking harder the more lines are cleared at once
+pub static SHAKE: Setting = Setting::new("shake", true);
+/// The board flashing on a Tetris
+pub static FLASH: Setting = Setting::new("flash", true);
+
+const ALL: [&Setting; 5] = [&PATTERNS, &PARTICLES, &SMOOTH, &SHAKE, &FLASH];
+
+/// Reads the saved settings, the ones that aren't saved keep their defaults
+pub fn load() {
+    for (key, value) in storage::parse_pairs(&storage::read(SETTINGS).unwrap_or_default()) {
+        if let Some(setting) = ALL.iter().find(|setting| setting.name == key) {
+            setting.on.store(value == "on", Ordering::Relaxed);
+        }
+    }
+}
+
+fn save() {
+    let settings = ALL
+        .iter()
+        .map(|setting| {
+            let value = if setting.is_on() { "on" } else { "off" };
+            format!("{} = {value}\n", setting.name)
+        })
+        .collect::<String>();
+    storage::write(SETTINGS, &settings);
+}
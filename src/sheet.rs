@@ -0,0 +1,154 @@
+use std::fs;
+
+use crate::{
+    constants::{num_cols, num_rows},
+    grid::GridValues,
+    storage,
+    tetromino::TType,
+};
+
+/// The plain text layout shared by puzzle, opener and theme files: `key = value` lines, followed
+/// by a board drawn one row per line for puzzles and openers. Everything after a `#` is a comment.
+///
+/// ```text
+/// name = Tetris Ready
+/// pieces = I O T
+/// board =
+/// XXXXXXXXX.
+/// ```
+pub struct Sheet<'a> {
+    fields: Vec<(&'a str, &'a str)>,
+    board: Vec<&'a str>,
+}
+
+impl<'a> Sheet<'a> {
+    pub fn parse(source: &'a str, board_key: Option<&str>, keys: &[&str]) -> Result<Self, String> {
+        let mut fields = Vec::new();
+        let mut board = Vec::new();
+        let mut in_board = false;
+
+        for line in source
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or("").trim())
+            .filter(|line| !line.is_empty())
+        {
+            if in_board {
+                board.push(line);
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .ok_or_else(|| format!("expected `key = value`, found `{line}`"))?;
+            if Some(key) == board_key {
+                in_board = true;
+            } else if keys.contains(&key) {
+                fields.push((key, value));
+            } else {
+                return Err(format!("unknown key `{key}`"));
+            }
+        }
+
+        Ok(Self { fields, board })
+    }
+
+    pub fn get(&self, key: &str) -> Result<&'a str, String> {
+        self.fields
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, value)| *value)
+            .ok_or_else(|| format!("missing {key}"))
+    }
+
+    pub fn pieces(&self, key: &str) -> Result<Vec<TType>, String> {
+        let pieces = self
+            .get(key)?
+            .split_whitespace()
+            .flat_map(str::chars)
+            .map(|c| match TType::from_char(c) {
+                Some(TType::Garbage) | None => Err(format!("unknown piece `{c}`")),
+                Some(piece) => Ok(piece),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if pieces.is_empty() {
+            return Err(format!("{key} needs at least one piece"));
+        }
+        Ok(pieces)
+    }
+
+    /// Rows are `.` for empty, a piece letter for a coloured block or `X` for garbage, and are
+    /// aligned to the bottom of the grid
+    pub fn board(&self) -> Result<GridValues, String> {
+        if self.board.len() > num_rows() {
+            return Err(format!("board has more than {} rows", num_rows()));
+        }
+        let mut values = vec![vec![None; num_cols()]; num_rows() - self.board.len()];
+        for line in &self.board {
+            if line.chars().count() != num_cols() {
+                return Err(format!(
+                    "board row `{line}` is not {} cells wide",
+                    num_cols()
+                ));
+            }
+            values.push(
+                line.chars()
+                    .map(|c| match c {
+                        '.' => Ok(None),
+                        c => TType::from_char(c)
+                            .map(Some)
+                            .ok_or_else(|| format!("unknown cell `{c}`")),
+                    })
+                    .collect::<Result<_, _>>()?,
+            );
+        }
+        Ok(values)
+    }
+}
+
+/// The reverse of [`Sheet::board`], empty rows above the highest block are left out
+pub fn write_board(values: &GridValues) -> String {
+    values
+        .iter()
+        .skip_while(|row| row.iter().all(Option::is_none))
+        .map(|row| {
+            let mut line = row
+                .iter()
+                .map(|cell| cell.map_or('.', TType::to_char))
+                .collect::<String>();
+            line.push('\n');
+            line
+        })
+        .collect()
+}
+
+/// Sheets in one file are separated by `---` lines, invalid ones are reported and skipped
+pub fn parse_all<T>(source: &str, origin: &str, parse: fn(&str) -> Result<T, String>) -> Vec<T> {
+    source
+        .split("\n---")
+        .filter(|chunk| !chunk.trim().is_empty())
+        .filter_map(|chunk| {
+            parse(chunk)
+                .map_err(|err| eprintln!("Skipping entry in {origin}: {err}"))
+                .ok()
+        })
+        .collect()
+}
+
+/// The built-in sheets followed by every `.txt` file in `~/.tetrs/<dir>`
+pub fn load_all<T>(builtin: &str, dir: &str, parse: fn(&str) -> Result<T, String>) -> Vec<T> {
+    let mut all = parse_all(builtin, "built-in list", parse);
+    if let Ok(entries) = fs::read_dir(storage::data_dir().join(dir)) {
+        let mut paths = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+            .collect::<Vec<_>>();
+        paths.sort();
+        for path in paths {
+            if let Ok(source) = fs::read_to_string(&path) {
+                all.extend(parse_all(&source, &path.display().to_string(), parse));
+            }
+        }
+    }
+    all
+}
@@ -0,0 +1,195 @@
+//! Plays games without a window as fast as they can go, for comparing rule changes and AI
+//! heuristics over many games. Games are played by the built-in AI or replayed from a file, and
+//! each one is played out frame by frame through `Player::update`, gravity included, the same way
+//! it would be on screen.
+//!
+//! A replay holds one or more games, each a `SEED <n>` line followed by the `PLACE` line of every
+//! piece in the order they were placed, as written by `--record`.
+
+use std::fs;
+
+use crate::{
+    ai::{self, Placement},
+    animator::AnimationRegulator,
+    args::{Format, SimArgs},
+    constants::{num_cols, num_rows},
+    data::GameData,
+    grid::Grid,
+    player::{Input, Player},
+    selector::Selector,
+    sounds::Sounds,
+};
+
+// The AI rarely tops out, so its games need an end
+const AI_MAX_PIECES: u32 = 1000;
+
+struct Stats {
+    seed: u64,
+    score: u32,
+    lines: u32,
+    pieces: u32,
+    tetrises: u32,
+}
+
+impl Stats {
+    /// Share of the lines that were cleared four at a time
+    fn tetris_rate(&self) -> f32 {
+        match self.lines {
+            0 => 0.,
+            lines => (self.tetrises * 4) as f32 / lines as f32,
+        }
+    }
+
+    fn encode(&self, game: usize, format: Format) -> String {
+        let Stats {
+            seed,
+            score,
+            lines,
+            pieces,
+            tetrises,
+        } = self;
+        let rate = self.tetris_rate();
+        match format {
+            Format::Csv => format!("{game},{seed},{score},{lines},{pieces},{tetrises},{rate:.3}"),
+            Format::Json => format!(
+                "{{\"game\":{game},\"seed\":{seed},\"score\":{score},\"lines\":{lines},\"pieces\":{pieces},\"tetrises\":{tetrises},\"tetris_rate\":{rate:.3}}}"
+            ),
+        }
+    }
+}
+
+/// Plays one game dealt from `seed`, placing each piece where `choose` says until it has no
+/// answer, the stack tops out or `max_pieces` have been placed
+fn play(
+    seed: u64,
+    max_pieces: u32,
+    mut choose: impl FnMut(&Player) -> Option<Placement>,
+) -> (Stats, Vec<Placement>) {
+    let (sounds, mut reg) = (Sounds::silent(), AnimationRegulator::headless());
+    // Always an empty board, in debug builds too where the default one comes filled in
+    let grid = Grid::from_values(vec![vec![None; num_cols()]; num_rows()]);
+    let mut player = Player::new(grid, Selector::seeded(seed), GameData::default());
+    let mut placements = Vec::new();
+    let mut tetrises = 0;
+
+    while player.data.pieces < max_pieces {
+        let Some(placement) = choose(&player) else {
+            break;
+        };
+        placements.push(placement);
+        let mut presses = placement.presses(&player.selector.current);
+        loop {
+            let input = presses.pop_front().unwrap_or(Input {
+                soft_drop: true,
+                ..Default::default()
+            });
+            if player.update(input, &sounds) {
+                break;
+            }
+        }
+
+        let locked_out = player.is_locked_out();
+        player.place();
+        let cleared = player.grid.check_complete(&mut reg);
+        player.data.inc_score(&sounds, cleared);
+        if cleared == 4 {
+            tetrises += 1;
+        }
+        if !player.spawn_next() || locked_out {
+            break;
+        }
+    }
+
+    let stats = Stats {
+        seed,
+        score: player.data.score,
+        lines: player.data.lines,
+        pieces: player.data.pieces,
+        tetrises,
+    };
+    (stats, placements)
+}
+
+/// The seed and placements of every game in a replay
+fn load_replay(path: &str) -> Result<Vec<(u64, Vec<Placement>)>, String> {
+    let text = fs::read_to_string(path).map_err(|err| format!("could not read {path}: {err}"))?;
+    let mut games: Vec<(u64, Vec<Placement>)> = Vec::new();
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        if let Some(seed) = line.strip_prefix("SEED ") {
+            let seed = seed
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid seed `{line}`"))?;
+            games.push((seed, Vec::new()));
+            continue;
+        }
+        let (_, placements) = games
+            .last_mut()
+            .ok_or_else(|| format!("{path} has to start with a SEED line"))?;
+        placements.push(Placement::parse(line)?);
+    }
+    Ok(games)
+}
+
+pub fn run(args: SimArgs) -> Result<(), String> {
+    let results = match &args.replay {
+        Some(path) => load_replay(path)?
+            .into_iter()
+            .map(|(seed, placements)| {
+                let mut placements = placements.into_iter();
+                play(seed, args.max_pieces.unwrap_or(u32::MAX), |_| {
+                    placements.next()
+                })
+            })
+            .collect::<Vec<_>>(),
+        None => (0..args.games as u64)
+            .map(|n| {
+                let max_pieces = args.max_pieces.unwrap_or(AI_MAX_PIECES);
+                play(args.seed.wrapping_add(n), max_pieces, |player| {
+                    ai::best_placement(player).map(|(placement, _)| placement)
+                })
+            })
+            .collect(),
+    };
+
+    if let Some(path) = &args.record {
+        let replay = results
+            .iter()
+            .flat_map(|(stats, placements)| {
+                std::iter::once(format!("SEED {}", stats.seed))
+                    .chain(placements.iter().map(Placement::encode))
+            })
+            .collect::<Vec<_>>();
+        fs::write(path, replay.join("\n") + "\n")
+            .map_err(|err| format!("could not write {path}: {err}"))?;
+    }
+
+    if args.format == Format::Csv {
+        println!("game,seed,score,lines,pieces,tetrises,tetris_rate");
+    }
+    for (game, (stats, _)) in results.iter().enumerate() {
+        println!("{}", stats.encode(game, args.format));
+    }
+
+    // A summary for whoever is watching, kept off stdout so the output stays easy to parse
+    let count = results.len().max(1) as f32;
+    let mean = |field: fn(&Stats) -> u32| {
+        results
+            .iter()
+            .map(|(stats, _)| field(stats) as f32)
+            .sum::<f32>()
+            / count
+    };
+    let (lines, tetrises) = (mean(|s| s.lines), mean(|s| s.tetrises));
+    eprintln!(
+        "{} games: {:.0} score, {lines:.1} lines, {:.1} pieces and a {:.1}% tetris rate on average",
+        results.len(),
+        mean(|s| s.score),
+        mean(|s| s.pieces),
+        match lines > 0. {
+            true => tetrises * 4. / lines * 100.,
+            false => 0.,
+        }
+    );
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use macroquad::audio::Sound;
+use macroquad::audio::{play_sound_once, Sound};
 
 pub struct Sounds {
     values: Vec<Sound>,
@@ -27,7 +27,18 @@ impl Sounds {
         Self { values }
     }
 
+    /// No sounds at all, for running games without a window
+    pub fn silent() -> Self {
+        Self { values: Vec::new() }
+    }
+
     pub fn get(&self, id: usize) -> &Sound {
         &self.values[id]
     }
+
+    pub fn play(&self, id: usize) {
+        if let Some(sound) = self.values.get(id) {
+            play_sound_once(sound);
+        }
+    }
 }
@@ -0,0 +1,38 @@
+use std::{fs, path::PathBuf};
+
+/// Directory where tetrs keeps everything it saves between runs (`~/.tetrs`, or the working
+/// directory when no home directory is known)
+pub fn data_dir() -> PathBuf {
+    match std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")) {
+        Some(home) => PathBuf::from(home).join(".tetrs"),
+        None => PathBuf::from("."),
+    }
+}
+
+pub fn read(name: &str) -> Option<String> {
+    fs::read_to_string(data_dir().join(name)).ok()
+}
+
+/// Returns whether the file was written, failures are also reported on stderr
+pub fn write(name: &str, contents: &str) -> bool {
+    let path = data_dir().join(name);
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Err(err) = fs::write(&path, contents) {
+        eprintln!("Could not save {}: {err}", path.display());
+        return false;
+    }
+    true
+}
+
+/// Parses `key = value` lines, skipping blanks and `#` comments
+pub fn parse_pairs(contents: &str) -> Vec<(&str, &str)> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .collect()
+}
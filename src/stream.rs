@@ -0,0 +1,145 @@
+//! Broadcasts the live game over plain TCP for stream overlays and other tools. Anyone can
+//! connect, and every frame each of them is sent the whole game as one line of JSON:
+//!
+//! ```text
+//! {"state":"playing","mode":"classic","players":[{
+//!     "board":["..........", ..., "XXXX.XXXXX"],   rows top to bottom, X is garbage
+//!     "piece":{"type":"T","rotation":0,"cells":[[-1,4],[0,3],[0,4],[0,5]]},
+//!     "ghost":[[18,3],[18,4],[18,5],[19,4]],
+//!     "next":["S"],
+//!     "score":0,"level":0,"lines":0,"incoming":0}]}
+//! ```
+//!
+//! Versus games have one entry in `players` for each board. Cells are `[row, col]`, with negative
+//! rows in the hidden rows above the board, and the board is always the `num_rows()` visible rows
+//! as strings of `num_cols()` characters.
+
+use std::{
+    io::{ErrorKind, Write},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::{
+    data::{GameMode, GameState},
+    player::Player,
+    tetromino::Tetromino,
+};
+
+pub const DEFAULT_PORT: u16 = 7879;
+
+// A client that has fallen this far behind is dropped instead of buffering forever
+const MAX_PENDING: usize = 1 << 20;
+
+struct Client {
+    stream: TcpStream,
+    pending: Vec<u8>,
+}
+
+impl Client {
+    /// Returns false once the client has gone away or stopped reading
+    fn send(&mut self, frame: &str) -> bool {
+        self.pending.extend(frame.bytes());
+        self.pending.push(b'\n');
+        while !self.pending.is_empty() {
+            match self.stream.write(&self.pending) {
+                Ok(0) => return false,
+                Ok(n) => drop(self.pending.drain(..n)),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => return false,
+            }
+        }
+        self.pending.len() < MAX_PENDING
+    }
+}
+
+pub struct StreamServer {
+    listener: TcpListener,
+    clients: Vec<Client>,
+}
+
+impl StreamServer {
+    pub fn bind(address: &str) -> Result<Self, String> {
+        let listener = TcpListener::bind(address)
+            .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+            .map_err(|err| format!("could not stream on {address}: {err}"))?;
+        Ok(Self {
+            listener,
+            clients: Vec::new(),
+        })
+    }
+
+    /// Lets in anyone who connected since the last frame, then sends `frame` to everyone
+    pub fn broadcast(&mut self, frame: &str) {
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                self.clients.push(Client {
+                    stream,
+                    pending: Vec::new(),
+                });
+            }
+        }
+        self.clients.retain_mut(|client| client.send(frame));
+    }
+}
+
+fn cells(piece: &Tetromino) -> String {
+    let cells = piece
+        .get_curr_positions()
+        .iter()
+        .map(|tile| format!("[{},{}]", tile.row as i32, tile.col as i32))
+        .collect::<Vec<_>>();
+    format!("[{}]", cells.join(","))
+}
+
+fn player(player: &Player) -> String {
+    let board = player
+        .grid
+        .values()
+        .iter()
+        .map(|row| {
+            let row = row
+                .iter()
+                .map(|cell| cell.map_or('.', |t| t.to_char()))
+                .collect::<String>();
+            format!("\"{row}\"")
+        })
+        .collect::<Vec<_>>();
+    let next = player
+        .selector
+        .preview()
+        .iter()
+        .map(|t| format!("\"{}\"", t.to_char()))
+        .collect::<Vec<_>>();
+    let piece = &player.selector.current;
+    format!(
+        "{{\"board\":[{}],\"piece\":{{\"type\":\"{}\",\"rotation\":{},\"cells\":{}}},\"ghost\":{},\"next\":[{}],\"score\":{},\"level\":{},\"lines\":{},\"incoming\":{}}}",
+        board.join(","),
+        piece._type.to_char(),
+        piece.rotation as usize,
+        cells(piece),
+        cells(&player.selector.ghost),
+        next.join(","),
+        player.data.score,
+        player.data.level,
+        player.data.lines,
+        player.incoming,
+    )
+}
+
+/// The JSON line sent for one frame
+pub fn frame(state: &GameState, mode: GameMode, players: &[Player]) -> String {
+    let state = match state {
+        GameState::Menu => "menu",
+        GameState::Playing => "playing",
+        GameState::GameOver => "game_over",
+        GameState::Solved => "solved",
+        GameState::Editing => "editing",
+        GameState::Connecting => "connecting",
+    };
+    let players = players.iter().map(player).collect::<Vec<_>>();
+    format!(
+        "{{\"state\":\"{state}\",\"mode\":\"{}\",\"players\":[{}]}}",
+        mode.name().to_lowercase(),
+        players.join(",")
+    )
+}
@@ -1,11 +1,11 @@
 use macroquad::prelude::*;
 
-use crate::animator::AnimationRegulator;
-use crate::constants::*;
+use crate::animator::{Anim, AnimationRegulator};
 use crate::data::Vec2;
-use crate::{CELL_SIZE, POSSIBLE_POSITIONS};
+use crate::theme::{draw_block, draw_hidden_block, theme};
+use crate::{cell_size, num_cols, POSSIBLE_POSITIONS};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TType {
     I,
     O,
@@ -14,23 +14,57 @@ pub enum TType {
     S,
     Z,
     J,
+    /// Only ever used for cells on the grid, never as a falling piece
+    Garbage,
 }
 
-impl From<TType> for Color {
-    fn from(value: TType) -> Self {
+impl TType {
+    pub const PIECES: [TType; 7] = [
+        TType::I,
+        TType::O,
+        TType::T,
+        TType::L,
+        TType::S,
+        TType::Z,
+        TType::J,
+    ];
+
+    pub fn from_char(c: char) -> Option<Self> {
+        use TType::*;
+        Some(match c.to_ascii_uppercase() {
+            'I' => I,
+            'O' => O,
+            'T' => T,
+            'L' => L,
+            'S' => S,
+            'Z' => Z,
+            'J' => J,
+            'X' | 'G' => Garbage,
+            _ => return None,
+        })
+    }
+
+    pub fn to_char(self) -> char {
         use TType::*;
-        match value {
-            I => *GOLDEN_GRASS,
-            O => *BURNT_SIENNA2,
-            T => *CASCADE,
-            L => *CLAY_CREEK,
-            S => *FLAME_PEA,
-            Z => *BURNT_SIENNA1,
-            J => *RAVEN,
+        match self {
+            I => 'I',
+            O => 'O',
+            T => 'T',
+            L => 'L',
+            S => 'S',
+            Z => 'Z',
+            J => 'J',
+            Garbage => 'X',
         }
     }
 }
 
+impl From<TType> for Color {
+    fn from(value: TType) -> Self {
+        theme().piece(value)
+    }
+}
+
 #[rustfmt::skip]
 #[derive(Debug, Clone, Copy)]
 pub enum Rotation {
@@ -38,6 +72,13 @@ pub enum Rotation {
 }
 
 impl Rotation {
+    pub const ALL: [Rotation; 4] = [
+        Rotation::State1,
+        Rotation::State2,
+        Rotation::State3,
+        Rotation::State4,
+    ];
+
     pub fn next(&self) -> Self {
         use Rotation::*;
         match self {
@@ -73,11 +114,13 @@ pub struct Tetromino {
 }
 
 impl Tetromino {
+    /// A piece in its spawn position, in the two hidden rows right above the visible field
     pub fn new(_type: TType) -> Self {
+        let centred = |width: usize| ((num_cols() - width) / 2) as f32;
         let offset = match _type {
-            TType::I => Vec2::new(-1., 3.),
-            TType::O => Vec2::new(0., 4.),
-            _ => Vec2::new(0., 3.),
+            TType::I => Vec2::new(-2., centred(4)),
+            TType::O => Vec2::new(-2., centred(2)),
+            _ => Vec2::new(-2., centred(3)),
         };
 
         Self {
@@ -105,38 +148,62 @@ impl Tetromino {
         self.offset = self.offset + vec
     }
 
+    /// The cells in the visible field
+    fn visible_positions(&self) -> impl Iterator<Item = Vec2> {
+        self.get_curr_positions()
+            .into_iter()
+            .filter(|tile| tile.row >= 0.)
+    }
+
     pub async fn draw(&self) {
-        for tile in self.get_curr_positions() {
-            draw_rectangle(
-                tile.col * CELL_SIZE + 11.,
-                tile.row * CELL_SIZE + 11.,
-                CELL_SIZE - 1.,
-                CELL_SIZE - 1.,
-                self._type.into(),
-            )
+        self.draw_tiles(&self.get_curr_positions());
+    }
+
+    /// Draws the piece with its tiles at `tiles`, which can be between cells, instead of where it is.
+    /// Tiles in the hidden rows only peek out above the board.
+    pub fn draw_tiles(&self, tiles: &[Vec2]) {
+        for tile in tiles {
+            let (x, y) = (tile.col * cell_size() + 11., tile.row * cell_size() + 11.);
+            match tile.row >= 0. {
+                true => draw_block(x, y, cell_size() - 1., self._type, self._type.into()),
+                false => draw_hidden_block(x, y, cell_size() - 1., self._type.into()),
+            }
         }
     }
 
     pub async fn draw_outline(&self, reg: &AnimationRegulator) {
-        for tile in self.get_curr_positions() {
+        for tile in self.visible_positions() {
             draw_rectangle_lines(
-                tile.col * CELL_SIZE + 11.,
-                tile.row * CELL_SIZE + 11.,
-                CELL_SIZE - 1.,
-                CELL_SIZE - 1.,
+                tile.col * cell_size() + 11.,
+                tile.row * cell_size() + 11.,
+                cell_size() - 1.,
+                cell_size() - 1.,
                 5.,
-                reg.colour_blink_map(1, self._type.into()),
+                reg.blink_colour(Anim::Ghost, self._type.into()),
             )
         }
     }
 
-    pub async fn draw_with_offset(&self, offset: Vec2) {
+    /// A see-through piece, for showing where something could go
+    pub fn draw_hint(&self) {
+        for tile in self.visible_positions() {
+            let (x, y) = (tile.col * cell_size() + 11., tile.row * cell_size() + 11.);
+            let mut colour: Color = self._type.into();
+            colour.a = 0.25;
+            draw_rectangle(x, y, cell_size() - 1., cell_size() - 1., colour);
+            colour.a = 0.6;
+            draw_rectangle_lines(x, y, cell_size() - 1., cell_size() - 1., 2., colour);
+        }
+    }
+
+    /// Draws the piece away from the board, with its cells `size` apart from `offset` on
+    pub async fn draw_with_offset(&self, offset: Vec2, size: f32) {
         for tile in self.get_curr_positions() {
-            draw_rectangle(
-                offset.col + tile.col * CELL_SIZE,
-                offset.row + tile.row * CELL_SIZE,
-                CELL_SIZE - 1.,
-                CELL_SIZE - 1.,
+            draw_block(
+                offset.col + tile.col * size,
+                offset.row + tile.row * size,
+                size - 1.,
+                self._type,
                 self._type.into(),
             )
         }
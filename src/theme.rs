@@ -0,0 +1,233 @@
+use std::{fs, sync::OnceLock};
+
+use macroquad::prelude::*;
+
+use crate::{
+    constants::FONT,
+    settings::PATTERNS,
+    sheet::{self, Sheet},
+    storage,
+    tetromino::TType,
+};
+
+const BUILTIN_THEMES: &str = include_str!("../resources/themes.txt");
+
+// Skins that can be used by name instead of a path
+const BUILTIN_SKINS: [(&str, &[u8]); 1] =
+    [("bevel", include_bytes!("../resources/skins/bevel.png"))];
+
+const COLOUR_KEYS: [&str; 7] = [
+    "background",
+    "cell",
+    "accent",
+    "text",
+    "value",
+    "dim",
+    "shadow",
+];
+const PIECE_KEYS: [&str; 8] = ["I", "O", "T", "L", "S", "Z", "J", "garbage"];
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// Colours, font and block skin the game is drawn with
+#[derive(Clone)]
+pub struct Theme {
+    pub name: String,
+    pub background: Color,
+    // Empty cells and the boxes of the side panel
+    pub cell: Color,
+    // Titles and banners
+    pub accent: Color,
+    // Labels
+    pub text: Color,
+    // Numbers and other values next to the labels
+    pub value: Color,
+    // Hints and menu entries that aren't selected
+    pub dim: Color,
+    // Behind banners and the game over screen
+    pub shadow: Color,
+    // In the order of `PIECE_KEYS`
+    pieces: [Color; 8],
+    font: Option<String>,
+    skin: Option<String>,
+    // Loaded once the theme is picked
+    font_data: &'static [u8],
+    skin_texture: Option<Texture2D>,
+}
+
+fn parse_colour(value: &str) -> Result<Color, String> {
+    u32::from_str_radix(value, 16)
+        .ok()
+        .filter(|_| value.len() == 6)
+        .map(Color::from_hex)
+        .ok_or_else(|| format!("invalid colour `{value}`, colours are written like e8be82"))
+}
+
+impl Theme {
+    /// Parses a single theme, see [`Sheet`] for the layout. Colours are six hex digits, and the
+    /// font and skin are optional paths relative to `~/.tetrs/themes`. The skin is an image
+    /// drawn for every block, tinted with the colour of its piece, and `bevel` is built in:
+    ///
+    /// ```text
+    /// name = Tetrs
+    /// background = 2d211e
+    /// cell = 452923
+    /// accent = e55d4d
+    /// text = e8be82
+    /// value = e8d8a5
+    /// dim = b16b4a
+    /// shadow = 202020
+    /// I = de9a28
+    /// ...
+    /// garbage = 6d3d29
+    /// font = fonts/MyFont.otf
+    /// skin = bevel
+    /// ```
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let keys = [&["name", "font", "skin"][..], &COLOUR_KEYS, &PIECE_KEYS].concat();
+        let sheet = Sheet::parse(source, None, &keys)?;
+        let colour = |key: &str| sheet.get(key).and_then(parse_colour);
+        let mut pieces = [BLACK; 8];
+        for (piece, key) in pieces.iter_mut().zip(PIECE_KEYS) {
+            *piece = colour(key)?;
+        }
+        Ok(Self {
+            name: sheet.get("name")?.to_owned(),
+            background: colour("background")?,
+            cell: colour("cell")?,
+            accent: colour("accent")?,
+            text: colour("text")?,
+            value: colour("value")?,
+            dim: colour("dim")?,
+            shadow: colour("shadow")?,
+            pieces,
+            font: sheet.get("font").ok().map(str::to_owned),
+            skin: sheet.get("skin").ok().map(str::to_owned),
+            font_data: FONT,
+            skin_texture: None,
+        })
+    }
+
+    /// The built-in themes followed by every `.txt` file in `~/.tetrs/themes`
+    pub fn load_all() -> Vec<Self> {
+        sheet::load_all(BUILTIN_THEMES, "themes", Self::parse)
+    }
+
+    pub fn piece(&self, ty: TType) -> Color {
+        self.pieces[ty as usize]
+    }
+
+    /// The font to draw text with
+    pub fn font(&self) -> &'static [u8] {
+        self.font_data
+    }
+
+    /// Reads the font and skin files, which needs a window for the skin
+    fn load_files(&mut self) -> Result<(), String> {
+        let dir = storage::data_dir().join("themes");
+        if let Some(font) = &self.font {
+            let path = dir.join(font);
+            let data = fs::read(&path)
+                .map_err(|err| format!("could not read font {}: {err}", path.display()))?;
+            // Loaded once for the whole run
+            self.font_data = Box::leak(data.into_boxed_slice());
+        }
+        if let Some(skin) = &self.skin {
+            let data = match BUILTIN_SKINS.iter().find(|(name, _)| name == skin) {
+                Some((_, data)) => data.to_vec(),
+                None => {
+                    let path = dir.join(skin);
+                    fs::read(&path)
+                        .map_err(|err| format!("could not read skin {}: {err}", path.display()))?
+                }
+            };
+            let texture = Texture2D::from_file_with_format(&data, None);
+            texture.set_filter(FilterMode::Nearest);
+            self.skin_texture = Some(texture);
+        }
+        Ok(())
+    }
+}
+
+/// Picks the theme called `name`, or the first built-in one, for the rest of the run
+pub fn init(name: Option<&str>) -> Result<(), String> {
+    let themes = Theme::load_all();
+    let mut theme = match name {
+        Some(name) => themes
+            .iter()
+            .find(|theme| theme.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                let names = themes.iter().map(|theme| theme.name.as_str());
+                format!(
+                    "no theme called `{name}`, pick one of {}",
+                    names.collect::<Vec<_>>().join(", ")
+                )
+            })?,
+        None => themes.first().ok_or("there are no themes")?,
+    }
+    .clone();
+    theme.load_files()?;
+    let _ = THEME.set(theme);
+    Ok(())
+}
+
+pub fn theme() -> &'static Theme {
+    THEME.get_or_init(|| Theme::parse(BUILTIN_THEMES.split("\n---").next().unwrap()).unwrap())
+}
+
+/// Draws one block of `ty` with the skin of the theme, or as a plain square without one, and
+/// its pattern when those are on
+pub fn draw_block(x: f32, y: f32, size: f32, ty: TType, colour: Color) {
+    match &theme().skin_texture {
+        Some(texture) => draw_texture_ex(
+            texture,
+            x,
+            y,
+            colour,
+            DrawTextureParams {
+                dest_size: Some(vec2(size, size)),
+                ..Default::default()
+            },
+        ),
+        None => draw_rectangle(x, y, size, size, colour),
+    }
+    if PATTERNS.is_on() {
+        draw_pattern(x, y, size, ty, colour.a);
+    }
+}
+
+/// The bottom of a block in the hidden rows, the part of it that shows in the gap above the board.
+/// `y` is where the whole block would be drawn.
+pub fn draw_hidden_block(x: f32, y: f32, size: f32, colour: Color) {
+    let (top, bottom) = (y.max(0.), (y + size).min(10.));
+    if top < bottom {
+        draw_rectangle(x, top, size, bottom - top, colour);
+    }
+}
+
+/// A glyph for every piece, so none of them have to be told apart by colour alone
+fn draw_pattern(x: f32, y: f32, size: f32, ty: TType, alpha: f32) {
+    let colour = Color::new(0., 0., 0., 0.55 * alpha);
+    let thickness = (size / 8.).max(1.);
+    let (lo, hi) = (size * 0.25, size * 0.75);
+    let (centre_x, centre_y) = (x + size / 2., y + size / 2.);
+    match ty {
+        TType::I => draw_line(centre_x, y + lo, centre_x, y + hi, thickness, colour),
+        TType::O => draw_rectangle_lines(x + lo, y + lo, hi - lo, hi - lo, thickness, colour),
+        TType::T => draw_triangle_lines(
+            vec2(centre_x, y + lo),
+            vec2(x + hi, y + hi),
+            vec2(x + lo, y + hi),
+            thickness,
+            colour,
+        ),
+        TType::L => draw_circle(centre_x, centre_y, size * 0.15, colour),
+        TType::S => draw_line(x + lo, y + hi, x + hi, y + lo, thickness, colour),
+        TType::Z => draw_line(x + lo, y + lo, x + hi, y + hi, thickness, colour),
+        TType::J => draw_circle_lines(centre_x, centre_y, size * 0.25, thickness, colour),
+        TType::Garbage => {
+            draw_line(x + lo, y + lo, x + hi, y + hi, thickness, colour);
+            draw_line(x + lo, y + hi, x + hi, y + lo, thickness, colour);
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use macroquad::{prelude::*, rand::gen_range};
+use macroquad_text::Fonts;
+
+use crate::{
+    animator::AnimationRegulator,
+    constants::*,
+    data::{GameData, Vec2},
+    grid::Grid,
+    net::{Connection, Message},
+    player::{Controls, Player},
+    selector::Selector,
+    sounds::Sounds,
+    tetromino::Rotation,
+    theme::theme,
+};
+
+// Player one plays on the left with WASD, player two on the right with the arrow keys
+const CONTROLS: [Controls; 2] = [Controls::WASD, Controls::ARROWS];
+
+/// What locking a piece in a versus game led to
+struct Locked {
+    attack: u32,
+    // (rows, hole) when incoming garbage came up
+    garbage: Option<(usize, usize)>,
+    topped_out: bool,
+}
+
+/// Locks the current piece, lines it sends first cancel out incoming garbage, and the rest of
+/// that garbage comes up when nothing was cleared
+fn lock(player: &mut Player, sounds: &Sounds, reg: &mut AnimationRegulator) -> Locked {
+    let tspin = player.is_tspin();
+    let locked_out = player.is_locked_out();
+    player.grid.land(&player.selector.current, reg);
+    player.place();
+    let cleared = player.grid.check_complete(reg);
+    player.data.inc_score(sounds, cleared);
+
+    let mut attack = attack(cleared, tspin, player.grid.is_clear());
+    let cancelled = attack.min(player.incoming);
+    player.incoming -= cancelled;
+    attack -= cancelled;
+
+    let mut garbage = None;
+    let mut topped_out = locked_out;
+    if cleared == 0 && player.incoming > 0 {
+        let (rows, hole) = (player.incoming as usize, gen_range(0, num_cols()));
+        topped_out |= !player.grid.add_garbage(rows, hole);
+        player.incoming = 0;
+        garbage = Some((rows, hole));
+    }
+
+    if !player.spawn_next() {
+        topped_out = true;
+    } else if !topped_out {
+        sounds.play(4);
+    }
+    Locked {
+        attack,
+        garbage,
+        topped_out,
+    }
+}
+
+/// Garbage lines sent for a clear
+pub fn attack(cleared: usize, tspin: bool, perfect_clear: bool) -> u32 {
+    let table = match tspin {
+        true => &TSPIN_ATTACK_TABLE[..],
+        false => &ATTACK_TABLE[..],
+    };
+    let lines = table.get(cleared).copied().unwrap_or(0);
+    match perfect_clear && cleared > 0 {
+        true => lines + PERFECT_CLEAR_ATTACK,
+        false => lines,
+    }
+}
+
+/// Maps the single player layout onto part of the screen, `scale` times the size with its top
+/// left corner at `x`, `y`
+fn board_camera(screen: &Camera2D, x: f32, y: f32, scale: f32) -> Camera2D {
+    Camera2D {
+        zoom: screen.zoom * scale,
+        target: (screen.target - vec2(x, y)) / scale,
+        render_target: screen.render_target.clone(),
+        ..Default::default()
+    }
+}
+
+/// Two boards side by side. Online, the first one is played here and the second one mirrors
+/// the other side from what it sends over `connection`.
+pub struct Versus {
+    pub players: [Player; 2],
+    pub sent: [u32; 2],
+    pub winner: Option<usize>,
+    connection: Option<Connection>,
+    // Set when the connection failed, with what went wrong
+    pub disconnected: Option<String>,
+    // The last position of our falling piece the other side was told about
+    last_move: Option<Message>,
+}
+
+impl Default for Versus {
+    fn default() -> Self {
+        Self::new(Selector::default, None)
+    }
+}
+
+impl Versus {
+    fn new(selector: impl Fn() -> Selector, connection: Option<Connection>) -> Self {
+        let player = || {
+            Player::new(
+                Grid::from_values(vec![vec![None; num_cols()]; num_rows()]),
+                selector(),
+                GameData::default(),
+            )
+        };
+        Self {
+            players: [player(), player()],
+            sent: [0; 2],
+            winner: None,
+            connection,
+            disconnected: None,
+            last_move: None,
+        }
+    }
+
+    /// Both boards deal from `seed`, so the mirrored board gets the same pieces as the real one
+    pub fn online(connection: Connection, seed: u64) -> Self {
+        Self::new(|| Selector::seeded(seed), Some(connection))
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.connection.is_some()
+    }
+
+    pub fn name(&self, n: usize) -> String {
+        match (self.is_online(), n) {
+            (true, 0) => "YOU".to_owned(),
+            (true, _) => "THEM".to_owned(),
+            _ => format!("P{}", n + 1),
+        }
+    }
+
+    /// Runs a frame for both players, returns true once the game is over
+    pub fn update(&mut self, sounds: &Sounds, reg: &mut AnimationRegulator) -> bool {
+        if self.is_online() {
+            if let Err(err) = self.update_online(sounds, reg) {
+                self.disconnected = Some(err);
+            }
+            return self.winner.is_some() || self.disconnected.is_some();
+        }
+
+        for (n, controls) in CONTROLS.iter().enumerate() {
+            if self.players[n].update(controls.poll(), sounds) {
+                let locked = lock(&mut self.players[n], sounds, reg);
+                self.sent[n] += locked.attack;
+                self.players[1 - n].incoming += locked.attack;
+                if locked.topped_out {
+                    self.winner = Some(1 - n);
+                }
+            }
+            self.players[n].update_ghost();
+            if self.winner.is_some() {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn update_online(
+        &mut self,
+        sounds: &Sounds,
+        reg: &mut AnimationRegulator,
+    ) -> Result<(), String> {
+        let connection = self.connection.as_mut().unwrap();
+        let [local, remote] = &mut self.players;
+
+        if local.update(Controls::ARROWS.poll(), sounds) {
+            let piece = local.selector.current;
+            let locked = lock(local, sounds, reg);
+            connection.send(Message::Lock {
+                piece: piece._type,
+                rotation: piece.rotation as usize,
+                row: piece.offset.row as i32,
+                col: piece.offset.col as i32,
+                incoming: local.incoming,
+            })?;
+            if let Some((rows, hole)) = locked.garbage {
+                connection.send(Message::Garbage { rows, hole })?;
+            }
+            if locked.attack > 0 {
+                connection.send(Message::Attack(locked.attack))?;
+                self.sent[0] += locked.attack;
+                // Until their next lock says how much of it they cancelled
+                remote.incoming += locked.attack;
+            }
+            if locked.topped_out {
+                connection.send(Message::Lost)?;
+                self.winner = Some(1);
+                return Ok(());
+            }
+        }
+        local.update_ghost();
+
+        let current = local.selector.current;
+        let position = Message::Move {
+            rotation: current.rotation as usize,
+            row: current.offset.row as i32,
+            col: current.offset.col as i32,
+        };
+        if self.last_move != Some(position) {
+            connection.send(position)?;
+            self.last_move = Some(position);
+        }
+
+        // Nothing after a LOST matters, not even the connection closing straight after it
+        while self.winner.is_none() {
+            let Some(message) = connection.receive()? else {
+                break;
+            };
+            match message {
+                Message::Move { rotation, row, col } => {
+                    remote.selector.current.rotation = Rotation::ALL[rotation];
+                    remote.selector.current.offset = Vec2::new(row as f32, col as f32);
+                }
+                Message::Lock {
+                    piece,
+                    rotation,
+                    row,
+                    col,
+                    incoming,
+                } => {
+                    if remote.selector.current._type != piece {
+                        return Err("the boards went out of sync".to_owned());
+                    }
+                    remote.selector.current.rotation = Rotation::ALL[rotation];
+                    remote.selector.current.offset = Vec2::new(row as f32, col as f32);
+                    if !remote.is_block_inside() {
+                        return Err("the boards went out of sync".to_owned());
+                    }
+                    remote.grid.land(&remote.selector.current, reg);
+                    remote.place();
+                    let cleared = remote.grid.check_complete(reg);
+                    remote.data.inc_score(sounds, cleared);
+                    remote.spawn_next();
+                    remote.incoming = incoming;
+                }
+                Message::Garbage { rows, hole } => {
+                    remote.grid.add_garbage(rows, hole.min(num_cols() - 1));
+                }
+                Message::Attack(lines) => {
+                    // Whatever the other side claims, it can't overflow the count
+                    self.sent[1] = self.sent[1].saturating_add(lines);
+                    local.incoming = local.incoming.saturating_add(lines);
+                }
+                Message::Lost => self.winner = Some(0),
+                message => return Err(format!("unexpected {}", message.encode())),
+            }
+        }
+        remote.update_ghost();
+        Ok(())
+    }
+
+    pub async fn draw(
+        &mut self,
+        fonts: &Fonts<'_>,
+        reg: &mut AnimationRegulator,
+        camera: &Camera2D,
+    ) {
+        for n in 0..2 {
+            let name = self.name(n);
+            let player = &mut self.players[n];
+            // Everything below is in single player coordinates, with the top bar above the board
+            set_camera(&board_camera(
+                camera,
+                n as f32 * window_width() as f32 / 2. + 8.,
+                170.,
+                versus_scale(),
+            ));
+
+            player.grid.draw(reg).await;
+            player.draw_pieces(reg).await;
+            player.grid.draw_row_collapse_animation(reg);
+
+            // Incoming garbage meter along the right edge of the board
+            let height = player.incoming.min(num_rows() as u32) as f32 * cell_size();
+            let (meter_x, bottom) = (board_width() - 6., num_rows() as f32 * cell_size() + 10.);
+            draw_rectangle(meter_x, 11., 10., bottom - 11., theme().cell);
+            draw_rectangle(meter_x, bottom - height, 10., height, theme().accent);
+
+            fonts.draw_text(&name, 11., -230., 60, theme().text);
+            fonts.draw_text(
+                &format!("LINES: {}", player.data.lines),
+                11.,
+                -150.,
+                28,
+                theme().value,
+            );
+            fonts.draw_text(
+                &format!("SENT: {}", self.sent[n]),
+                11.,
+                -115.,
+                28,
+                theme().value,
+            );
+            let next_x = (board_width() - 141.).max(181.);
+            draw_rectangle(next_x, -225., 130., 140., theme().cell);
+            player.draw_next(next_x, -225., 1.).await;
+        }
+        set_camera(camera);
+    }
+}